@@ -0,0 +1,136 @@
+//! Compares evaluating a node by walking the tree against evaluating its compiled form,
+//! with and without simplifying it first, as is done many times over in a sweep. Run with
+//! `cargo bench`.
+
+use physcaulc::{
+    eval::{self, Environment},
+    f64plus::FloatPlus,
+    parse,
+    value::{Derivatives, Quantity, SIDimension, Value},
+};
+use std::{collections::HashMap, hint::black_box, time::Instant};
+
+const ITERATIONS: usize = 200_000;
+
+fn environment() -> Environment {
+    let unit = |dim| eval::ConversionValue { factor: 1., dim };
+    let mut env = Environment {
+        consts: HashMap::new(),
+        formulas: HashMap::new(),
+        evaluators: HashMap::new(),
+        prefixes: HashMap::from_iter(eval::units::si_prefixes()),
+        log_units: HashMap::new(),
+        units: HashMap::from([
+            ("s".into(), unit(SIDimension::base(SIDimension::TIME))),
+            ("m".into(), unit(SIDimension::base(SIDimension::LENGTH))),
+            ("g".into(), unit(SIDimension::base(SIDimension::MASS))),
+        ]),
+        dimensions: Default::default(),
+        angle_mode: eval::AngleMode::Dimensionless,
+        limits: eval::EvalLimits::default(),
+        parallelism: Default::default(),
+        sources: Default::default(),
+        parent: None,
+    };
+    env.units.get_mut("g").unwrap().factor = 1e-3;
+
+    for line in [
+        "g0 = 9.81 [m/s²]",
+        "ke(m, v) = m v² / 2",
+        "pe(m, h) = m g0 h",
+        "energy(m, v, h) = ke(m, v) + pe(m, h)",
+    ] {
+        let statement = parse::parse_line(line).unwrap();
+        statement.execute(&mut env, &HashMap::new()).unwrap();
+    }
+    env
+}
+
+fn quantity(value: f64, dim: SIDimension) -> Value {
+    Quantity {
+        value: FloatPlus::Scalar(value),
+        derivatives: Derivatives::new(),
+        dim,
+    }
+    .into()
+}
+
+fn compare(env: &Environment, params: &[String], expression: &str, inputs: &[Vec<Value>]) {
+    let node = parse::parse(parse::lex(expression).unwrap()).unwrap();
+    let compiled = node.compile(params);
+    let simplified = env.simplify(&node).node().compile(params);
+
+    let named_inputs = inputs
+        .iter()
+        .map(|args| params.iter().cloned().zip(args.iter().cloned()).collect())
+        .collect::<Vec<HashMap<String, Value>>>();
+
+    let time = Instant::now();
+    let tree_results = named_inputs
+        .iter()
+        .map(|args| black_box(node.eval(env, black_box(args))))
+        .collect::<Vec<_>>();
+    let tree_time = time.elapsed();
+
+    let time = Instant::now();
+    let compiled_results = inputs
+        .iter()
+        .map(|args| black_box(compiled.eval(env, black_box(args))))
+        .collect::<Vec<_>>();
+    let compiled_time = time.elapsed();
+
+    let time = Instant::now();
+    let simplified_results = inputs
+        .iter()
+        .map(|args| black_box(simplified.eval(env, black_box(args))))
+        .collect::<Vec<_>>();
+    let simplified_time = time.elapsed();
+
+    for (i, tree) in tree_results.iter().enumerate() {
+        assert_eq!(format!("{tree:?}"), format!("{:?}", compiled_results[i]));
+        assert_eq!(format!("{tree:?}"), format!("{:?}", simplified_results[i]));
+    }
+
+    println!("{expression}");
+    println!("  tree: {tree_time:?}");
+    println!(
+        "  compiled: {compiled_time:?}, {:.2}x",
+        tree_time.as_secs_f64() / compiled_time.as_secs_f64()
+    );
+    println!(
+        "  simplified and compiled: {simplified_time:?}, {:.2}x",
+        tree_time.as_secs_f64() / simplified_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let env = environment();
+    let params = ["m", "v", "h"].map(String::from);
+    let inputs = (0..ITERATIONS)
+        .map(|i| {
+            let x = i as f64 / ITERATIONS as f64;
+            vec![
+                quantity(1. + x, SIDimension::base(SIDimension::MASS)),
+                quantity(3. - x, SIDimension::base(SIDimension::LENGTH))
+                    .div(&quantity(1., SIDimension::base(SIDimension::TIME)))
+                    .unwrap(),
+                quantity(10. * x, SIDimension::base(SIDimension::LENGTH)),
+            ]
+        })
+        .collect::<Vec<Vec<Value>>>();
+
+    println!("{ITERATIONS} evaluations");
+    compare(
+        &env,
+        &params,
+        "ke(m, v) + m g0 h + (m - 500 [g]) v² / 4 + sin(h / 3 [km]) m g0 h",
+        &inputs,
+    );
+    // Mostly calls of user functions, which call others in turn
+    compare(
+        &env,
+        &params,
+        "energy(m, v, h) + energy(2 m, v, h / 2)",
+        &inputs,
+    );
+}
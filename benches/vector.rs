@@ -0,0 +1,91 @@
+//! Sweeps over a million points, timing each expression on vectors for every parameter.
+//! Run with `cargo bench --bench vector`, and compare the times between changes.
+
+use physcaulc::{
+    eval::{self, Environment},
+    f64plus::FloatPlus,
+    parse,
+    value::{Derivatives, Quantity, SIDimension, Value},
+};
+use std::{
+    collections::HashMap,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const POINTS: usize = 1_000_000;
+const REPEATS: usize = 5;
+
+// Name and expression of each case, in terms of the parameters `x` and `y`
+const CASES: [(&str, &str); 7] = [
+    ("lookup", "x"),
+    ("arithmetic", "x y² / 2 + 3 x - y"),
+    ("functions", "sin(x) exp(y) + sqrt(x y)"),
+    ("uncertain", "(x ± 0.01) (y ± 0.02) / (x + y ± 0.1)"),
+    ("complex", "sqrt(x - y) (x + y)"),
+    ("comparison", "(x ± 0.01) ~= (y ± 0.01)"),
+    ("conditional", "if(x > y, x y, x / y)"),
+];
+
+fn environment() -> Environment {
+    Environment {
+        consts: HashMap::new(),
+        formulas: HashMap::new(),
+        evaluators: HashMap::new(),
+        prefixes: HashMap::new(),
+        log_units: HashMap::new(),
+        units: HashMap::new(),
+        dimensions: Default::default(),
+        angle_mode: eval::AngleMode::Dimensionless,
+        limits: eval::EvalLimits::default(),
+        parallelism: Default::default(),
+        sources: Default::default(),
+        parent: None,
+    }
+}
+
+fn sweep(f: impl Fn(f64) -> f64) -> Value {
+    let points = (0..POINTS)
+        .map(|i| f(i as f64 / POINTS as f64))
+        .collect::<Vec<f64>>();
+
+    Quantity {
+        value: FloatPlus::from(points),
+        derivatives: Derivatives::new(),
+        dim: SIDimension::DIMLESS,
+    }
+    .into()
+}
+
+// Shortest of several runs, which is the least disturbed by anything else going on
+fn best_time(mut f: impl FnMut()) -> Duration {
+    (0..REPEATS)
+        .map(|_| {
+            let time = Instant::now();
+            f();
+            time.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let env = environment();
+    let params = ["x", "y"].map(String::from);
+    let args = [sweep(|t| 1. + t), sweep(|t| 2. - 2. * t)];
+
+    println!(
+        "{POINTS} points on {} threads, best of {REPEATS}",
+        env.parallelism.threads
+    );
+    for (name, expr) in CASES {
+        let node = parse::parse(parse::lex(expr).unwrap()).unwrap();
+        let compiled = node.compile(&params);
+        compiled.eval(&env, &args).unwrap();
+
+        let time = best_time(|| {
+            black_box(compiled.eval(&env, black_box(&args)).unwrap());
+        });
+        println!("{name:<12} {time:?}");
+    }
+}
@@ -0,0 +1,98 @@
+use crate::value::{Quantity, Value, ValueError};
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy)]
+pub enum Builtin {
+    Unary(fn(&Value) -> Result<Value, ValueError>),
+    Binary(fn(&Value, &Value) -> Result<Value, ValueError>),
+}
+
+const BUILTINS: [(&str, Builtin); 35] = [
+    ("exp", Builtin::Unary(Value::exp)),
+    ("ln", Builtin::Unary(Value::natlog)),
+    ("log10", Builtin::Unary(Value::log10)),
+    ("log2", Builtin::Unary(Value::log2)),
+    ("log", Builtin::Binary(Value::log)),
+    ("sqrt", Builtin::Unary(Value::sqrt)),
+    ("cbrt", Builtin::Unary(Value::cbrt)),
+    ("abs", Builtin::Unary(|v| Ok(v.abs()))),
+    ("sin", Builtin::Unary(Value::sin)),
+    ("cos", Builtin::Unary(Value::cos)),
+    ("tan", Builtin::Unary(Value::tan)),
+    ("asin", Builtin::Unary(Value::asin)),
+    ("acos", Builtin::Unary(Value::acos)),
+    ("atan", Builtin::Unary(Value::atan)),
+    ("atan2", Builtin::Binary(Value::atan2)),
+    ("sinh", Builtin::Unary(Value::sinh)),
+    ("cosh", Builtin::Unary(Value::cosh)),
+    ("tanh", Builtin::Unary(Value::tanh)),
+    ("asinh", Builtin::Unary(Value::asinh)),
+    ("acosh", Builtin::Unary(Value::acosh)),
+    ("atanh", Builtin::Unary(Value::atanh)),
+    ("gamma", Builtin::Unary(Value::gamma)),
+    ("lgamma", Builtin::Unary(Value::lgamma)),
+    ("erf", Builtin::Unary(Value::erf)),
+    ("erfc", Builtin::Unary(Value::erfc)),
+    ("besselj", Builtin::Binary(|n, x| x.bessel_j(n))),
+    ("bessely", Builtin::Binary(|n, x| x.bessel_y(n))),
+    ("besseli", Builtin::Binary(|n, x| x.bessel_i(n))),
+    ("besselk", Builtin::Binary(|n, x| x.bessel_k(n))),
+    ("airyai", Builtin::Unary(Value::airy_ai)),
+    ("airybi", Builtin::Unary(Value::airy_bi)),
+    ("airyaiprime", Builtin::Unary(Value::airy_ai_prime)),
+    ("airybiprime", Builtin::Unary(Value::airy_bi_prime)),
+    ("lambertw", Builtin::Unary(Value::lambert_w0)),
+    ("lambertwm1", Builtin::Unary(Value::lambert_wm1)),
+];
+
+// Constants available everywhere, shadowed by parameters and user constants
+const BUILTIN_CONSTS: [(&str, f64); 2] = [("pi", PI), ("π", PI)];
+
+pub fn get_builtin(name: &str) -> Option<Builtin> {
+    BUILTINS
+        .iter()
+        .find(|&&(builtin_name, _)| builtin_name == name)
+        .map(|&(_, builtin)| builtin)
+}
+
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|&(name, _)| name)
+}
+
+pub fn get_builtin_const(name: &str) -> Option<Value> {
+    BUILTIN_CONSTS
+        .iter()
+        .find(|&&(const_name, _)| const_name == name)
+        .map(|&(_, value)| Quantity::from_float(value).into())
+}
+
+pub fn builtin_const_names() -> impl Iterator<Item = &'static str> {
+    BUILTIN_CONSTS.iter().map(|&(name, _)| name)
+}
+
+/// Whether the builtin takes an angle, which may also be dimensionless.
+pub fn takes_angle(name: &str) -> bool {
+    matches!(name, "sin" | "cos" | "tan")
+}
+
+/// Whether the result of the builtin is an angle in radians.
+pub fn returns_angle(name: &str) -> bool {
+    matches!(name, "asin" | "acos" | "atan" | "atan2")
+}
+
+impl Builtin {
+    pub fn param_count(&self) -> usize {
+        match self {
+            Self::Unary(_) => 1,
+            Self::Binary(_) => 2,
+        }
+    }
+
+    pub fn call(&self, params: &[Value]) -> Result<Value, ValueError> {
+        // it is assumed that the amount of variables passed is correct
+        match self {
+            Self::Unary(f) => f(&params[0]),
+            Self::Binary(f) => f(&params[0], &params[1]),
+        }
+    }
+}
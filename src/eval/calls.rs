@@ -0,0 +1,173 @@
+use super::{
+    BinaryOp, CallLimit, Environment, EvalLimits, EvaluationError, Evaluator, Node, NodeContent,
+    NodeError, NodeErrorContent, ShadowWarning,
+};
+use std::sync::Arc;
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        EvalLimits {
+            max_depth: 200,
+            fuel: 1_000_000,
+        }
+    }
+}
+
+/// What is left of the limits while evaluating one expression, along with the user
+/// functions currently being called.
+pub struct CallBudget {
+    max_depth: usize,
+    fuel: usize,
+    initial_fuel: usize,
+    chain: Vec<String>,
+}
+
+impl CallBudget {
+    pub fn new(limits: &EvalLimits) -> Self {
+        CallBudget {
+            max_depth: limits.max_depth,
+            fuel: limits.fuel,
+            initial_fuel: limits.fuel,
+            chain: Vec::new(),
+        }
+    }
+
+    // Every node evaluated uses one step of fuel
+    pub fn use_fuel(&mut self, start: usize, end: usize) -> Result<(), NodeError> {
+        if self.fuel == 0 {
+            return Err(self.limit_error(CallLimit::Fuel(self.initial_fuel), start, end));
+        }
+
+        self.fuel -= 1;
+        Ok(())
+    }
+
+    pub fn enter(&mut self, func: &str, start: usize, end: usize) -> Result<(), NodeError> {
+        self.chain.push(func.into());
+        if self.chain.len() > self.max_depth {
+            let error = self.limit_error(CallLimit::Depth(self.max_depth), start, end);
+            self.chain.pop();
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.chain.pop();
+    }
+
+    fn limit_error(&self, limit: CallLimit, start: usize, end: usize) -> NodeError {
+        NodeError {
+            content: NodeErrorContent::CallLimitError(limit, self.chain.clone()),
+            start,
+            end,
+        }
+    }
+}
+
+// Calls made whenever the node is evaluated, those only made by one branch of an `if` or
+// the right side of `and` and `or` are left out as the other branch may be a base case
+fn unconditional_calls<'a>(node: &'a Node, calls: &mut Vec<&'a Node>) {
+    match &node.content {
+        NodeContent::Binary(lhs, BinaryOp::And | BinaryOp::Or, _) => {
+            unconditional_calls(lhs, calls)
+        }
+        NodeContent::Binary(lhs, _, rhs) | NodeContent::Uncertain(lhs, rhs, _) => {
+            unconditional_calls(lhs, calls);
+            unconditional_calls(rhs, calls);
+        }
+        NodeContent::Unary(_, operand) => unconditional_calls(operand, calls),
+        NodeContent::Function(_, param_nodes) => {
+            calls.push(node);
+            for param_node in param_nodes {
+                unconditional_calls(param_node, calls);
+            }
+        }
+        NodeContent::Conditional(condition, _, _) => unconditional_calls(condition, calls),
+        NodeContent::Value(_) | NodeContent::Variable(_) => (),
+    }
+}
+
+fn called_name(call: &Node) -> &str {
+    match &call.content {
+        NodeContent::Function(func, _) => func,
+        _ => unreachable!(),
+    }
+}
+
+// The chain of unconditional calls from `from` back to `target`, if there is one
+fn find_cycle<'a>(
+    evaluators: &impl Fn(&str) -> Option<&'a Evaluator>,
+    from: &str,
+    target: &str,
+    chain: &mut Vec<String>,
+) -> bool {
+    if from == target {
+        return true;
+    }
+    if chain.iter().any(|name| name == from) {
+        return false;
+    }
+    let evaluator = match evaluators(from) {
+        Some(evaluator) => evaluator,
+        None => return false,
+    };
+
+    chain.push(from.into());
+    let mut calls = Vec::new();
+    unconditional_calls(&evaluator.parent, &mut calls);
+    for call in calls {
+        if find_cycle(evaluators, called_name(call), target, chain) {
+            return true;
+        }
+    }
+    chain.pop();
+    false
+}
+
+impl Environment {
+    /// Adds or replaces the user function `name`.
+    ///
+    /// A function which always calls itself, directly or through other user functions, can
+    /// never finish and is rejected. Recursion only reached through one branch of an `if`
+    /// is allowed, it is then up to the call limits to stop a missing base case. A body
+    /// which mixes dimensions whatever its arguments, or which does not give the declared
+    /// dimension, is rejected too. Hiding a built-in function or a global default gives a
+    /// warning.
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        mut evaluator: Evaluator,
+    ) -> Result<Option<ShadowWarning>, EvaluationError> {
+        let evaluators = |func: &str| {
+            if func == name {
+                Some(&evaluator)
+            } else {
+                self.get_evaluator(func)
+            }
+        };
+
+        let mut calls = Vec::new();
+        unconditional_calls(&evaluator.parent, &mut calls);
+        for call in calls {
+            let mut chain = vec![name.to_string()];
+            if find_cycle(&evaluators, called_name(call), name, &mut chain) {
+                chain.push(name.into());
+                return Err(EvaluationError {
+                    content: NodeErrorContent::UnboundedRecursion(chain),
+                    start: call.start,
+                    end: call.end,
+                    evalstr: evaluator.evalstr.clone(),
+                });
+            }
+        }
+
+        evaluator.signature = self.check_function_dimensions(name, &evaluator)?;
+
+        let warning = self.function_shadow_warning(name);
+        self.evaluators.insert(name.into(), Arc::new(evaluator));
+        self.recompute_dependents(name);
+        Ok(warning)
+    }
+}
@@ -0,0 +1,255 @@
+use super::{
+    CallBudget, Environment, Evaluator, Node, NodeContent, NodeError, NodeErrorContent,
+    node::{
+        eval_binary, eval_builtin_func, eval_conditional, eval_const, eval_unary, eval_uncertain,
+        unknown_var, user_func_args, user_func_result,
+    },
+};
+use crate::{f64plus::with_parallelism, value::Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, Weak},
+};
+
+type Step =
+    Box<dyn Fn(&Environment, &[Value], &mut CallBudget) -> Result<Value, NodeError> + Send + Sync>;
+
+// The body of a user function compiled with its parameters as the slots
+struct CompiledBody {
+    evaluator: Arc<Evaluator>,
+    step: Step,
+}
+
+// Bodies of the user functions called, compiled when first called. Calls reach them through
+// a weak reference as the bodies contain calls themselves.
+type Bodies = Mutex<HashMap<String, Arc<CompiledBody>>>;
+
+/// A node prepared for evaluating many times with different parameters, as in a sweep or a
+/// Monte Carlo run. Parameters are found by position rather than by name, and the tree is
+/// only walked once when compiling.
+///
+/// Results, errors and the use of the call limits are the same as those of `Node::eval`
+/// given the parameters by name. Constants and user functions are still looked up on each
+/// evaluation, so redefining them is seen as it would be by the node. The bodies of user
+/// functions are compiled as well, again whenever the function has been redefined.
+pub struct CompiledNode {
+    params: Vec<String>,
+    step: Step,
+    // Only held so the bodies live as long as the steps referring to them
+    _bodies: Arc<Bodies>,
+    start: usize,
+    end: usize,
+}
+
+impl Node {
+    /// Compiles the node for evaluating with the parameters `params`, in that order.
+    pub fn compile(&self, params: &[String]) -> CompiledNode {
+        let bodies = Arc::new(Bodies::default());
+        CompiledNode {
+            params: params.to_vec(),
+            step: compile(self, &params.into(), &Arc::downgrade(&bodies)),
+            _bodies: bodies,
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl CompiledNode {
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn eval(&self, env: &Environment, args: &[Value]) -> Result<Value, NodeError> {
+        if args.len() != self.params.len() {
+            return Err(NodeError {
+                content: NodeErrorContent::ParamCountError(self.params.len(), args.len()),
+                start: self.start,
+                end: self.end,
+            });
+        }
+
+        with_parallelism(env.parallelism, || {
+            (self.step)(env, args, &mut CallBudget::new(&env.limits))
+        })
+    }
+}
+
+// The compiled body of the user function `func`, which is compiled again once the function
+// has been redefined
+fn compiled_body(
+    bodies: &Weak<Bodies>,
+    func: &str,
+    evaluator: &Arc<Evaluator>,
+) -> Arc<CompiledBody> {
+    let shared = bodies
+        .upgrade()
+        .expect("compiled nodes outlive their evaluation");
+    if let Some(body) = shared.lock().unwrap().get(func)
+        && Arc::ptr_eq(&body.evaluator, evaluator)
+    {
+        return body.clone();
+    }
+
+    let body = Arc::new(CompiledBody {
+        evaluator: evaluator.clone(),
+        step: compile(
+            &evaluator.parent,
+            &evaluator.params.as_slice().into(),
+            bodies,
+        ),
+    });
+    shared.lock().unwrap().insert(func.into(), body.clone());
+    body
+}
+
+fn compile(node: &Node, params: &Arc<[String]>, bodies: &Weak<Bodies>) -> Step {
+    let (start, end) = (node.start, node.end);
+
+    match &node.content {
+        NodeContent::Value(val) => {
+            let val = val.clone();
+            Box::new(move |_, _, budget| {
+                budget.use_fuel(start, end)?;
+                Ok(val.clone())
+            })
+        }
+        NodeContent::Variable(var) => match params.iter().position(|param| param == var) {
+            Some(slot) => Box::new(move |_, args, budget| {
+                budget.use_fuel(start, end)?;
+                Ok(args[slot].clone())
+            }),
+            None => {
+                let (var, params) = (var.clone(), params.clone());
+                Box::new(move |env, args, budget| {
+                    budget.use_fuel(start, end)?;
+                    eval_const(&var, env, start, end).unwrap_or_else(|| {
+                        let named = params.iter().cloned().zip(args.iter().cloned()).collect();
+                        Err(unknown_var(&var, env, &named, start, end))
+                    })
+                })
+            }
+        },
+        NodeContent::Binary(lhs, op, rhs) => {
+            let (lhs, op, rhs) = (
+                compile(lhs, params, bodies),
+                *op,
+                compile(rhs, params, bodies),
+            );
+            Box::new(move |env, args, budget| {
+                budget.use_fuel(start, end)?;
+                eval_binary(
+                    &mut |budget: &mut CallBudget| lhs(env, args, budget),
+                    &mut |budget: &mut CallBudget| rhs(env, args, budget),
+                    op,
+                    budget,
+                    start,
+                    end,
+                )
+            })
+        }
+        NodeContent::Unary(op, operand) => {
+            let (op, operand) = (op.clone(), compile(operand, params, bodies));
+            Box::new(move |env, args, budget| {
+                budget.use_fuel(start, end)?;
+                eval_unary(
+                    &mut |budget: &mut CallBudget| operand(env, args, budget),
+                    &op,
+                    env,
+                    budget,
+                    start,
+                    end,
+                )
+            })
+        }
+        NodeContent::Function(func, param_nodes) => {
+            let arg_steps = param_nodes
+                .iter()
+                .map(|param_node| compile(param_node, params, bodies))
+                .collect::<Vec<Step>>();
+            // Only kept for the spans of arguments with the wrong dimension
+            let (func, param_nodes) = (func.clone(), param_nodes.clone());
+            let bodies = bodies.clone();
+            // The body first called from here, which is all that most calls need. Weak as the
+            // body may contain this call.
+            let first_body = OnceLock::<Weak<CompiledBody>>::new();
+            Box::new(move |env, args, budget| {
+                budget.use_fuel(start, end)?;
+                let mut eval_args = |budget: &mut CallBudget| {
+                    arg_steps
+                        .iter()
+                        .map(|step| step(env, args, budget))
+                        .collect()
+                };
+                let evaluator = match env.shared_evaluator(&func) {
+                    Some(evaluator) => evaluator,
+                    None => {
+                        return eval_builtin_func(
+                            &func,
+                            &param_nodes,
+                            &mut eval_args,
+                            env,
+                            budget,
+                            start,
+                            end,
+                        );
+                    }
+                };
+
+                let param_values =
+                    user_func_args(evaluator, &param_nodes, &mut eval_args, budget, start, end)?;
+                let body = match first_body.get().and_then(Weak::upgrade) {
+                    Some(body) if Arc::ptr_eq(&body.evaluator, evaluator) => body,
+                    _ => {
+                        let body = compiled_body(&bodies, &func, evaluator);
+                        first_body.get_or_init(|| Arc::downgrade(&body));
+                        body
+                    }
+                };
+                budget.enter(&func, start, end)?;
+                let result = (body.step)(env, &param_values, budget)
+                    .map_err(|e| e.to_evalerr(&evaluator.evalstr));
+                budget.leave();
+
+                user_func_result(&func, result, start, end)
+            })
+        }
+        NodeContent::Uncertain(value, sigma, source) => {
+            let (value, sigma) = (
+                compile(value, params, bodies),
+                compile(sigma, params, bodies),
+            );
+            let source = source.clone();
+            Box::new(move |env, args, budget| {
+                budget.use_fuel(start, end)?;
+                eval_uncertain(
+                    &mut |budget: &mut CallBudget| value(env, args, budget),
+                    &mut |budget: &mut CallBudget| sigma(env, args, budget),
+                    &source,
+                    env,
+                    budget,
+                    start,
+                    end,
+                )
+            })
+        }
+        NodeContent::Conditional(condition, if_true, if_false) => {
+            let condition = compile(condition, params, bodies);
+            let (if_true, if_false) = (
+                compile(if_true, params, bodies),
+                compile(if_false, params, bodies),
+            );
+            Box::new(move |env, args, budget| {
+                budget.use_fuel(start, end)?;
+                eval_conditional(
+                    &mut |budget: &mut CallBudget| condition(env, args, budget),
+                    &mut |budget: &mut CallBudget| if_true(env, args, budget),
+                    &mut |budget: &mut CallBudget| if_false(env, args, budget),
+                    budget,
+                    start,
+                    end,
+                )
+            })
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use super::{
+    AngleMode, BinaryOp, Environment, EvaluationError, Evaluator, Node, NodeContent, NodeError,
+    NodeErrorContent, UnaryOp, UnitExpr,
+    builtin::{get_builtin, get_builtin_const, returns_angle, takes_angle},
+    node::{eval_unit_conversion, split_log_unit},
+    signature::{DimensionPattern, Signature},
+    units::radian,
+};
+use crate::{
+    rational::Rational,
+    value::{SIDimension, Value, ValueError},
+};
+use std::collections::HashMap;
+
+// Parameters map to `None` when their dimension is only known once they are given a value
+type ParamDims = HashMap<String, Option<SIDimension>>;
+
+struct DimensionCheck<'a> {
+    env: &'a Environment,
+    // User functions whose bodies are being checked, a call to one of them again is left
+    // unknown rather than followed forever
+    calls: Vec<String>,
+    errors: Vec<NodeError>,
+}
+
+impl DimensionCheck<'_> {
+    fn error(&mut self, content: NodeErrorContent, start: usize, end: usize) {
+        self.errors.push(NodeError {
+            content,
+            start,
+            end,
+        });
+    }
+
+    fn value_error(&mut self, e: ValueError, start: usize, end: usize) {
+        self.error(NodeErrorContent::ValueError(e), start, end);
+    }
+
+    // A mismatch keeps the left dimension, so `1 [kg] + 2 [m] + 3 [s]` gives two errors
+    fn expect_equal(
+        &mut self,
+        l: Option<SIDimension>,
+        r: Option<SIDimension>,
+        start: usize,
+        end: usize,
+    ) -> Option<SIDimension> {
+        match (l, r) {
+            (Some(l), Some(r)) if l != r => {
+                self.value_error(ValueError::UnequalDimensions(l.clone(), r), start, end);
+                Some(l)
+            }
+            (Some(dim), _) | (_, Some(dim)) => Some(dim),
+            (None, None) => None,
+        }
+    }
+
+    fn expect_dimless(&mut self, dim: Option<SIDimension>, start: usize, end: usize) {
+        match dim {
+            Some(dim) if dim != SIDimension::DIMLESS => {
+                self.value_error(ValueError::NotDimensionlessOperand(dim), start, end)
+            }
+            _ => (),
+        }
+    }
+
+    // The trigonometric functions also take angles
+    fn expect_angle(&mut self, dim: Option<SIDimension>, start: usize, end: usize) {
+        match dim {
+            Some(dim) if !dim.is_angle_or_dimless() => {
+                self.value_error(ValueError::NotAngleOperand(dim), start, end)
+            }
+            _ => (),
+        }
+    }
+
+    fn infer(&mut self, node: &Node, params: &ParamDims) -> Option<SIDimension> {
+        let (start, end) = (node.start, node.end);
+        match &node.content {
+            NodeContent::Value(val) => Some(val.dim()),
+            NodeContent::Variable(var) => self.infer_var(var, params),
+            NodeContent::Binary(lhs, op, rhs) => {
+                self.infer_binary(lhs, *op, rhs, params, start, end)
+            }
+            NodeContent::Unary(op, operand) => {
+                let operand_dim = self.infer(operand, params);
+                self.infer_unary(op, operand_dim, start, end)
+            }
+            NodeContent::Function(func, param_nodes) => {
+                let arg_dims = param_nodes
+                    .iter()
+                    .map(|param_node| self.infer(param_node, params))
+                    .collect::<Vec<Option<SIDimension>>>();
+
+                match self.env.get_evaluator(func) {
+                    Some(evaluator) => {
+                        self.infer_call(func, evaluator, param_nodes, &arg_dims, start, end)
+                    }
+                    None => self.infer_builtin(func, &arg_dims, start, end),
+                }
+            }
+            NodeContent::Uncertain(value, sigma, _) => {
+                let value_dim = self.infer(value, params);
+                let sigma_dim = self.infer(sigma, params);
+                self.expect_equal(value_dim, sigma_dim, start, end)
+            }
+            NodeContent::Conditional(condition, if_true, if_false) => {
+                let condition_dim = self.infer(condition, params);
+                self.expect_dimless(condition_dim, start, end);
+                // Which value is taken is only known once the condition is evaluated, so
+                // the dimension is only known when both values agree on it
+                let true_dim = self.infer(if_true, params);
+                let false_dim = self.infer(if_false, params);
+                match (true_dim, false_dim) {
+                    (Some(true_dim), Some(false_dim)) if true_dim == false_dim => Some(true_dim),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // Unknown names are left for evaluation to report, they may be defined by then
+    fn infer_var(&self, var: &str, params: &ParamDims) -> Option<SIDimension> {
+        if let Some(dim) = params.get(var) {
+            return dim.clone();
+        }
+
+        match self.env.lookup_const(var) {
+            Some(Ok(value)) => Some(value.dim()),
+            Some(Err(_)) => None,
+            None => get_builtin_const(var).map(|value| value.dim()),
+        }
+    }
+
+    fn infer_binary(
+        &mut self,
+        lhs: &Node,
+        op: BinaryOp,
+        rhs: &Node,
+        params: &ParamDims,
+        start: usize,
+        end: usize,
+    ) -> Option<SIDimension> {
+        let left = self.infer(lhs, params);
+        let right = self.infer(rhs, params);
+
+        match op {
+            BinaryOp::Add | BinaryOp::Sub => self.expect_equal(left, right, start, end),
+            BinaryOp::Mul => Some(left?.mul(&right?)),
+            BinaryOp::Div => Some(left?.mul(&right?.reciprocal())),
+            BinaryOp::Pow => {
+                self.expect_dimless(right, start, end);
+                match self.constant_exponent(rhs, params) {
+                    Some(exponent) => left.map(|dim| dim.pow(exponent)),
+                    None => match left {
+                        Some(dim) if dim != SIDimension::DIMLESS => {
+                            self.value_error(ValueError::UnsupportedBaseDimension(dim), start, end);
+                            None
+                        }
+                        left => left,
+                    },
+                }
+            }
+            BinaryOp::Compare(_) => {
+                self.expect_equal(left, right, start, end);
+                Some(SIDimension::DIMLESS)
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                self.expect_dimless(left, start, end);
+                self.expect_dimless(right, start, end);
+                Some(SIDimension::DIMLESS)
+            }
+        }
+    }
+
+    // Only rational powers keep the dimension of the base, so `x^(1/2)` is fine for any x
+    // while `x^y` needs a dimensionless x
+    fn constant_exponent(&self, node: &Node, params: &ParamDims) -> Option<Rational> {
+        match &node.content {
+            &NodeContent::Value(Value::Rational(r)) => Some(r),
+            NodeContent::Variable(var) if !params.contains_key(var) => {
+                match self.env.get_const(var) {
+                    Some(&Value::Rational(r)) => Some(r),
+                    _ => None,
+                }
+            }
+            NodeContent::Unary(UnaryOp::Negative, operand) => {
+                self.constant_exponent(operand, params)?.checked_negative()
+            }
+            NodeContent::Binary(lhs, op, rhs) => {
+                let l = self.constant_exponent(lhs, params)?;
+                let r = self.constant_exponent(rhs, params)?;
+                match op {
+                    BinaryOp::Add => l.checked_add(r),
+                    BinaryOp::Sub => l.checked_sub(r),
+                    BinaryOp::Mul => l.checked_mul(r),
+                    BinaryOp::Div => l.checked_div(r),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn infer_unary(
+        &mut self,
+        op: &UnaryOp,
+        operand_dim: Option<SIDimension>,
+        start: usize,
+        end: usize,
+    ) -> Option<SIDimension> {
+        match op {
+            UnaryOp::Negative => operand_dim,
+            UnaryOp::Not => {
+                self.expect_dimless(operand_dim, start, end);
+                Some(SIDimension::DIMLESS)
+            }
+            UnaryOp::Units(units) => {
+                let (log_dim, conversion_dim) = self.infer_units(units)?;
+                match log_dim {
+                    Some(log_dim) => {
+                        self.expect_dimless(operand_dim, start, end);
+                        Some(log_dim.mul(&conversion_dim))
+                    }
+                    None => Some(operand_dim?.mul(&conversion_dim)),
+                }
+            }
+            UnaryOp::Convert(units) => {
+                let (log_dim, conversion_dim) = self.infer_units(units)?;
+                let expected = match log_dim {
+                    Some(log_dim) => log_dim.mul(&conversion_dim),
+                    None => conversion_dim,
+                };
+                self.expect_equal(operand_dim, Some(expected), start, end);
+                Some(SIDimension::DIMLESS)
+            }
+            // The value keeps its dimension, only its display is split
+            UnaryOp::ConvertMixed(unit_groups) => {
+                let mut dim = operand_dim.clone();
+                for units in unit_groups {
+                    match self.infer_units(units) {
+                        Some((Some(_), _)) => {
+                            let log_term = &units.terms[0];
+                            self.error(
+                                NodeErrorContent::MisplacedLogUnit(log_term.unit.clone()),
+                                log_term.start,
+                                log_term.end,
+                            );
+                        }
+                        Some((None, conversion_dim)) => {
+                            let operand_dim = operand_dim.clone();
+                            dim = self.expect_equal(operand_dim, Some(conversion_dim), start, end);
+                        }
+                        None => (),
+                    }
+                }
+                dim
+            }
+        }
+    }
+
+    // The dimension of the logarithmic unit if there is one, and of the other terms. Every
+    // unknown unit is reported rather than only the first.
+    fn infer_units(&mut self, units: &UnitExpr) -> Option<(Option<SIDimension>, SIDimension)> {
+        let (log_unit, linear_terms) = match split_log_unit(units, self.env) {
+            Ok(split) => split,
+            Err(e) => {
+                self.errors.push(e);
+                return None;
+            }
+        };
+
+        let mut dim = Some(SIDimension::DIMLESS);
+        for term in linear_terms {
+            match eval_unit_conversion(1., std::slice::from_ref(term), self.env) {
+                Ok(conversion) => dim = dim.map(|dim| dim.mul(&conversion.dim)),
+                Err(e) => {
+                    self.errors.push(e);
+                    dim = None;
+                }
+            }
+        }
+
+        Some((log_unit.map(|log_unit| log_unit.dim.clone()), dim?))
+    }
+
+    fn infer_call(
+        &mut self,
+        func: &str,
+        evaluator: &Evaluator,
+        arg_nodes: &[Node],
+        arg_dims: &[Option<SIDimension>],
+        start: usize,
+        end: usize,
+    ) -> Option<SIDimension> {
+        if evaluator.params.len() != arg_dims.len() {
+            self.error(
+                NodeErrorContent::ParamCountError(evaluator.params.len(), arg_dims.len()),
+                start,
+                end,
+            );
+            return None;
+        }
+
+        // A declared result stands for the body, which was checked against it on definition
+        let (bindings, mismatches) = evaluator.match_params(arg_dims);
+        for (i, declared) in &mismatches {
+            let i = *i;
+            self.error(
+                NodeErrorContent::ParamDimensionError(
+                    evaluator.params[i].clone(),
+                    declared.clone(),
+                    arg_dims[i].clone().unwrap(),
+                ),
+                arg_nodes[i].start,
+                arg_nodes[i].end,
+            );
+        }
+        let declared_result = evaluator.declared_result(&bindings);
+        if declared_result.is_some() || !mismatches.is_empty() {
+            return declared_result;
+        }
+        if self.calls.iter().any(|call| call == func) {
+            return None;
+        }
+
+        let params = evaluator
+            .params
+            .iter()
+            .cloned()
+            .zip(arg_dims.iter().cloned())
+            .collect::<ParamDims>();
+
+        let outer_errors = std::mem::take(&mut self.errors);
+        self.calls.push(func.into());
+        let result = self.infer(&evaluator.parent, &params);
+        self.calls.pop();
+        let body_errors = std::mem::replace(&mut self.errors, outer_errors);
+
+        for e in body_errors {
+            self.error(
+                NodeErrorContent::NestedError(
+                    func.into(),
+                    Box::new(e.to_evalerr(&evaluator.evalstr)),
+                ),
+                start,
+                end,
+            );
+        }
+        result
+    }
+
+    // Unknown functions are left for evaluation to report, like unknown variables
+    fn infer_builtin(
+        &mut self,
+        func: &str,
+        arg_dims: &[Option<SIDimension>],
+        start: usize,
+        end: usize,
+    ) -> Option<SIDimension> {
+        let builtin = get_builtin(func)?;
+        if builtin.param_count() != arg_dims.len() {
+            self.error(
+                NodeErrorContent::ParamCountError(builtin.param_count(), arg_dims.len()),
+                start,
+                end,
+            );
+            return None;
+        }
+
+        match func {
+            "sqrt" => return arg_dims[0].as_ref().map(|dim| dim.pow(Rational::new(1, 2))),
+            "cbrt" => return arg_dims[0].as_ref().map(|dim| dim.pow(Rational::new(1, 3))),
+            "abs" => return arg_dims[0].clone(),
+            "atan2" => {
+                self.expect_equal(arg_dims[0].clone(), arg_dims[1].clone(), start, end);
+            }
+            _ if takes_angle(func) => self.expect_angle(arg_dims[0].clone(), start, end),
+            _ => {
+                for dim in arg_dims {
+                    self.expect_dimless(dim.clone(), start, end);
+                }
+            }
+        }
+
+        if self.env.angle_mode == AngleMode::Strict && returns_angle(func) {
+            Some(radian().dim)
+        } else {
+            Some(SIDimension::DIMLESS)
+        }
+    }
+}
+
+impl Environment {
+    /// The dimension of the node found without evaluating it, given the dimensions of its
+    /// parameters. Errors are those evaluation would give for mismatched dimensions, all of
+    /// them rather than only the first.
+    ///
+    /// The dimension is `None` where it cannot be known ahead of evaluation, such as when it
+    /// depends on a name which is not defined yet or on which value of an `if` is taken. Those
+    /// are not errors here.
+    pub fn check_dimensions(
+        &self,
+        node: &Node,
+        params: &HashMap<String, SIDimension>,
+    ) -> Result<Option<SIDimension>, Vec<NodeError>> {
+        let params = params
+            .iter()
+            .map(|(name, dim)| (name.clone(), Some(dim.clone())))
+            .collect::<ParamDims>();
+
+        let mut check = DimensionCheck {
+            env: self,
+            calls: Vec::new(),
+            errors: Vec::new(),
+        };
+        let dim = check.infer(node, &params);
+
+        if check.errors.is_empty() {
+            Ok(dim)
+        } else {
+            Err(check.errors)
+        }
+    }
+
+    // Checks the body of a user function about to be defined as `name` against its declared
+    // dimensions. Parameters without one may have any dimension, so only mismatches which no
+    // arguments could avoid are found. Dimension variables are given base dimensions of
+    // their own which no unit uses, so `sq(x [D]) [D^2] = x³` is caught as well. Gives
+    // the declarations resolved for checking calls.
+    pub(super) fn check_function_dimensions(
+        &self,
+        name: &str,
+        evaluator: &Evaluator,
+    ) -> Result<Signature, EvaluationError> {
+        let mut check = DimensionCheck {
+            env: self,
+            calls: vec![name.into()],
+            errors: Vec::new(),
+        };
+
+        // Dimension variables are named in the errors along with the base dimensions
+        let mut dimensions = self.dimensions.clone();
+        let mut bindings = HashMap::new();
+        let mut variables = Vec::new();
+        let mut params = ParamDims::new();
+        let mut signature = Signature::default();
+        for (i, param) in evaluator.params.iter().enumerate() {
+            let annotation = match evaluator.param_dims.get(i) {
+                Some(Some(annotation)) => annotation,
+                _ => {
+                    params.insert(param.clone(), None);
+                    signature.params.push(None);
+                    continue;
+                }
+            };
+
+            let pattern = match DimensionPattern::new(annotation, self) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    check.errors.push(e);
+                    params.insert(param.clone(), None);
+                    signature.params.push(None);
+                    continue;
+                }
+            };
+            for (j, variable) in pattern.unbound(&bindings).enumerate() {
+                if j > 0 {
+                    check.error(
+                        NodeErrorContent::UnboundDimensionVariable(variable.name.clone()),
+                        variable.start,
+                        variable.end,
+                    );
+                }
+                variables.push(variable.name.clone());
+            }
+
+            let new_variable = pattern.unbound(&bindings).next().map(|v| v.name.clone());
+            if let Some(variable) = new_variable {
+                dimensions.names.push(variable.clone());
+                let free_dimension = dimensions.names.len() - 1;
+                bindings.insert(variable, SIDimension::base(free_dimension));
+            }
+            params.insert(param.clone(), pattern.substitute(&bindings));
+            signature.params.push(Some(pattern));
+        }
+
+        signature.result = evaluator.result_dim.as_ref().and_then(|annotation| {
+            DimensionPattern::new(annotation, self)
+                .map_err(|e| check.errors.push(e))
+                .ok()
+        });
+        let declared_result = evaluator.result_dim.as_ref().zip(signature.result.as_ref());
+        let declared_result = declared_result.map(|(annotation, pattern)| {
+            for variable in pattern.unbound(&bindings) {
+                if !variables.contains(&variable.name) {
+                    check.error(
+                        NodeErrorContent::UnboundDimensionVariable(variable.name.clone()),
+                        variable.start,
+                        variable.end,
+                    );
+                }
+            }
+            (pattern.substitute(&bindings), annotation)
+        });
+
+        let result = check.infer(&evaluator.parent, &params);
+        match (result, declared_result) {
+            (Some(result), Some((Some(declared), annotation))) if result != declared => {
+                check.value_error(
+                    ValueError::UnequalDimensions(result, declared),
+                    annotation.start,
+                    annotation.end,
+                );
+            }
+            _ => (),
+        }
+
+        if check.errors.is_empty() {
+            Ok(signature)
+        } else {
+            Err(EvaluationError {
+                content: NodeErrorContent::DimensionErrors(check.errors, dimensions),
+                start: evaluator.parent.start,
+                end: evaluator.parent.end,
+                evalstr: evaluator.evalstr.clone(),
+            })
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use super::{EvaluationError, NodeError};
+use super::{CallLimit, EvaluationError, NodeError, NodeErrorContent, ShadowWarning};
+use crate::value::DimensionTable;
 
 impl NodeError {
     pub fn to_evalerr(&self, s: &str) -> EvaluationError {
@@ -10,3 +11,105 @@ impl NodeError {
         }
     }
 }
+
+impl NodeErrorContent {
+    /// A message for the error, naming dimensions as `dims` does. Those of the environment
+    /// the error came from name the dimensions registered at runtime as well as the SI ones.
+    pub fn describe(&self, dims: &DimensionTable) -> String {
+        match self {
+            Self::ValueError(e) => e.describe(dims),
+            Self::VarNameError(name, similar) => unknown_name("variable", name, similar),
+            Self::FuncNameError(name, similar) => unknown_name("function", name, similar),
+            Self::UnitNameError(name, similar) => unknown_name("unit", name, similar),
+            Self::MisplacedLogUnit(unit) => {
+                format!("logarithmic unit `{unit}` must come first in its unit, to the power 1")
+            }
+            Self::UnitExistsError(name) => format!("unit `{name}` is already defined"),
+            Self::NotPlainUnitValue => {
+                "a unit must be a finite, non-zero real value without uncertainty".into()
+            }
+            Self::NegativeUnitValue => "a unit must be positive".into(),
+            Self::MixedUnitsOfVector => "only a single value can be split into units".into(),
+            Self::UnorderedMixedUnits => {
+                "units to split into must go from the largest to the smallest".into()
+            }
+            Self::ParamCountError(expected, found) => {
+                format!("expected {expected} arguments, found {found}")
+            }
+            Self::CallLimitError(limit, calls) => {
+                let limit = match limit {
+                    CallLimit::Depth(depth) => format!("call depth limit of {depth}"),
+                    CallLimit::Fuel(fuel) => format!("evaluation limit of {fuel} steps"),
+                };
+                if calls.is_empty() {
+                    format!("{limit} reached")
+                } else {
+                    format!("{limit} reached in {}", call_chain(calls))
+                }
+            }
+            Self::UnboundedRecursion(calls) => {
+                format!("function always calls itself: {}", calls.join(" -> "))
+            }
+            Self::SourceLimitError => "too many sources of uncertainty".into(),
+            Self::CyclicConstError(names) => {
+                format!("constant would use itself: {}", names.join(" -> "))
+            }
+            Self::FormulaError(name, e) => {
+                format!("formula of `{name}` failed: {}", e.content.describe(dims))
+            }
+            Self::DimensionErrors(errors, variable_dims) => errors
+                .iter()
+                .map(|e| e.content.describe(variable_dims))
+                .collect::<Vec<String>>()
+                .join("; "),
+            Self::ParamDimensionError(param, declared, found) => format!(
+                "parameter `{param}` is declared as {}, found {}",
+                dims.format(declared),
+                dims.format(found)
+            ),
+            Self::UnboundDimensionVariable(name) => {
+                format!("dimension variable `{name}` is not given by an earlier parameter")
+            }
+            Self::NestedError(func, e) => format!("in `{func}`: {}", e.content.describe(dims)),
+        }
+    }
+}
+
+impl ShadowWarning {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Builtin(name) => format!("`{name}` now hides the built-in `{name}`"),
+            Self::Default(name) => format!("`{name}` now hides the default `{name}`"),
+        }
+    }
+}
+
+// Calls at either end of a chain which are shown, with how many are left out in between
+const CHAIN_ENDS: usize = 3;
+
+fn call_chain(calls: &[String]) -> String {
+    if calls.len() <= 2 * CHAIN_ENDS + 1 {
+        return calls.join(" -> ");
+    }
+
+    let omitted = calls.len() - 2 * CHAIN_ENDS;
+    format!(
+        "{} -> … {omitted} more -> {}",
+        calls[..CHAIN_ENDS].join(" -> "),
+        calls[calls.len() - CHAIN_ENDS..].join(" -> ")
+    )
+}
+
+fn unknown_name(kind: &str, name: &str, similar: &[String]) -> String {
+    match similar {
+        [] => format!("unknown {kind} `{name}`"),
+        similar => format!(
+            "unknown {kind} `{name}`, did you mean {}?",
+            similar
+                .iter()
+                .map(|s| format!("`{s}`"))
+                .collect::<Vec<String>>()
+                .join(" or ")
+        ),
+    }
+}
@@ -0,0 +1,259 @@
+use super::{Environment, Formula, Node, NodeContent, NodeError, NodeErrorContent, ShadowWarning};
+use std::collections::{HashMap, HashSet};
+
+pub(super) fn children(node: &Node) -> Vec<&Node> {
+    match &node.content {
+        NodeContent::Binary(lhs, _, rhs) | NodeContent::Uncertain(lhs, rhs, _) => vec![lhs, rhs],
+        NodeContent::Unary(_, operand) => vec![operand],
+        NodeContent::Function(_, param_nodes) => param_nodes.iter().collect(),
+        NodeContent::Conditional(condition, if_true, if_false) => {
+            vec![condition, if_true, if_false]
+        }
+        NodeContent::Value(_) | NodeContent::Variable(_) => Vec::new(),
+    }
+}
+
+// Variables used by the node, along with the constants used by the user functions it calls.
+// Every function called on the way ends up in `visited_funcs`, built-in ones included.
+pub(super) fn collect_variables<'a>(
+    env: &'a Environment,
+    node: &'a Node,
+    params: &[String],
+    visited_funcs: &mut HashSet<&'a str>,
+    variables: &mut Vec<&'a Node>,
+) {
+    match &node.content {
+        NodeContent::Variable(var) if !params.contains(var) => variables.push(node),
+        NodeContent::Function(func, _) => {
+            if visited_funcs.insert(func)
+                && let Some(evaluator) = env.get_evaluator(func)
+            {
+                collect_variables(
+                    env,
+                    &evaluator.parent,
+                    &evaluator.params,
+                    visited_funcs,
+                    variables,
+                );
+            }
+        }
+        _ => (),
+    }
+
+    for child in children(node) {
+        collect_variables(env, child, params, visited_funcs, variables);
+    }
+}
+
+fn variable_name(node: &Node) -> &str {
+    match &node.content {
+        NodeContent::Variable(var) => var,
+        _ => unreachable!(),
+    }
+}
+
+// The variables used by a formula, along with the functions it calls in name order
+fn formula_uses<'a>(env: &'a Environment, node: &'a Node) -> (Vec<&'a Node>, Vec<String>) {
+    let mut variables = Vec::new();
+    let mut functions = HashSet::new();
+    collect_variables(env, node, &[], &mut functions, &mut variables);
+
+    let mut functions = functions
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    functions.sort();
+    (variables, functions)
+}
+
+fn dependency_names(variables: &[&Node]) -> Vec<String> {
+    let mut dependencies = variables
+        .iter()
+        .map(|variable| variable_name(variable).to_string())
+        .collect::<Vec<String>>();
+    dependencies.sort();
+    dependencies.dedup();
+    dependencies
+}
+
+impl Environment {
+    /// The formula defining the constant `name`, from the innermost scope defining it.
+    pub fn get_formula(&self, name: &str) -> Option<&Formula> {
+        self.scopes()
+            .find(|env| env.consts.contains_key(name) || env.formulas.contains_key(name))
+            .and_then(|env| env.formulas.get(name))
+    }
+
+    /// Constants that the formula of `name` uses, or `None` if `name` is not a formula.
+    pub fn dependencies(&self, name: &str) -> Option<&[String]> {
+        self.get_formula(name)
+            .map(|formula| formula.dependencies.as_slice())
+    }
+
+    /// Formulas which use the constant `name` directly, in name order.
+    pub fn dependents(&self, name: &str) -> Vec<String> {
+        let mut dependents = self
+            .visible_formulas()
+            .into_iter()
+            .filter(|(_, formula)| formula.dependencies.iter().any(|dep| dep == name))
+            .map(|(dependent, _)| dependent.to_string())
+            .collect::<Vec<String>>();
+        dependents.sort();
+        dependents
+    }
+
+    /// Defines the constant `name` by an expression, which is evaluated again whenever a
+    /// constant or function it uses is redefined in this scope.
+    ///
+    /// Formulas are evaluated in the scope they are defined in, so redefining a name in a
+    /// child scope does not change the formulas of its parents.
+    pub fn define_formula(
+        &mut self,
+        name: &str,
+        node: Node,
+    ) -> Result<Option<ShadowWarning>, NodeError> {
+        let (variables, functions) = formula_uses(self, &node);
+        for variable in &variables {
+            let mut chain = vec![name.to_string()];
+            if self.find_cycle(variable_name(variable), name, &mut chain) {
+                chain.push(name.into());
+                return Err(NodeError {
+                    content: NodeErrorContent::CyclicConstError(chain),
+                    start: variable.start,
+                    end: variable.end,
+                });
+            }
+        }
+
+        let value = node.eval(self, &HashMap::new())?;
+        let dependencies = dependency_names(&variables);
+
+        let warning = self.const_shadow_warning(name);
+        self.consts.remove(name);
+        self.formulas.insert(
+            name.into(),
+            Formula {
+                node,
+                dependencies,
+                functions,
+                value: Ok(value),
+            },
+        );
+        self.recompute_dependents(name);
+        Ok(warning)
+    }
+
+    // Whether `target` is reached from `from` through the dependencies of formulas
+    fn find_cycle(&self, from: &str, target: &str, chain: &mut Vec<String>) -> bool {
+        if from == target {
+            return true;
+        }
+        if chain.iter().any(|name| name == from) {
+            return false;
+        }
+        let formula = match self.get_formula(from) {
+            Some(formula) => formula,
+            None => return false,
+        };
+
+        chain.push(from.into());
+        for dependency in &formula.dependencies {
+            if self.find_cycle(dependency, target, chain) {
+                return true;
+            }
+        }
+        chain.pop();
+        false
+    }
+
+    // The error for the formula `name` if it uses itself through its dependencies
+    fn cycle_error(&self, name: &str) -> Option<NodeError> {
+        let formula = &self.formulas[name];
+        let mut chain = vec![name.to_string()];
+        formula
+            .dependencies
+            .iter()
+            .any(|dependency| self.find_cycle(dependency, name, &mut chain))
+            .then(|| {
+                chain.push(name.into());
+                NodeError {
+                    content: NodeErrorContent::CyclicConstError(chain),
+                    start: formula.node.start,
+                    end: formula.node.end,
+                }
+            })
+    }
+
+    // Evaluates the formulas of this scope affected by a change to the constant or function
+    // `changed` again, each one after the formulas it depends on. A redefined function may
+    // use other constants than before, so the formulas calling it collect theirs again.
+    pub(super) fn recompute_dependents(&mut self, changed: &str) {
+        let callers = self
+            .formulas
+            .iter()
+            .filter(|(_, formula)| formula.functions.iter().any(|func| func == changed))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+        for caller in callers {
+            let (variables, functions) = formula_uses(self, &self.formulas[&caller].node);
+            let dependencies = dependency_names(&variables);
+            let formula = self.formulas.get_mut(&caller).unwrap();
+            formula.dependencies = dependencies;
+            formula.functions = functions;
+        }
+
+        let mut affected = HashSet::from([changed.to_string()]);
+        loop {
+            let newly_affected = self
+                .formulas
+                .iter()
+                .filter(|(name, formula)| {
+                    !affected.contains(*name)
+                        && (formula
+                            .dependencies
+                            .iter()
+                            .any(|dep| affected.contains(dep))
+                            || formula.functions.iter().any(|func| func == changed))
+                })
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<String>>();
+            if newly_affected.is_empty() {
+                break;
+            }
+            affected.extend(newly_affected);
+        }
+        affected.remove(changed);
+
+        while !affected.is_empty() {
+            let ready = affected
+                .iter()
+                .find(|name| {
+                    self.formulas[*name]
+                        .dependencies
+                        .iter()
+                        .all(|dep| !affected.contains(dep))
+                })
+                .cloned();
+            let ready = match ready {
+                Some(ready) => ready,
+                // Only a redefined function can close a cycle, the formulas on it are left
+                // with an error until it is broken again
+                None => {
+                    let cyclic = affected
+                        .iter()
+                        .filter_map(|name| Some((name.clone(), self.cycle_error(name)?)))
+                        .collect::<Vec<_>>();
+                    for (name, error) in cyclic {
+                        self.formulas.get_mut(&name).unwrap().value = Err(error);
+                        affected.remove(&name);
+                    }
+                    continue;
+                }
+            };
+
+            let value = self.formulas[&ready].node.eval(self, &HashMap::new());
+            self.formulas.get_mut(&ready).unwrap().value = value;
+            affected.remove(&ready);
+        }
+    }
+}
@@ -1,11 +1,35 @@
+mod builtin;
+mod calls;
+mod compile;
+mod dimcheck;
 mod err;
+mod formula;
 mod node;
+mod repr;
+mod scope;
+mod signature;
+mod simplify;
+mod sources;
+mod statement;
+mod suggest;
+#[cfg(test)]
+mod tests;
+mod unitfile;
+pub mod units;
 
 use crate::{
+    f64plus::{Parallelism, with_parallelism},
     rational::Rational,
-    value::{SIDimension, Value, ValueError},
+    value::{Comparison, DimensionTable, SIDimension, SourceId, Value, ValueError},
+};
+use calls::CallBudget;
+pub use compile::CompiledNode;
+use signature::Signature;
+pub use simplify::Simplified;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, RwLock, Weak},
 };
-use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug)]
 pub enum BinaryOp {
@@ -14,6 +38,9 @@ pub enum BinaryOp {
     Mul,
     Div,
     Pow,
+    Compare(Comparison),
+    And,
+    Or,
 }
 
 #[derive(Debug, Clone)]
@@ -24,10 +51,21 @@ pub struct UnitTerm {
     pub power: Rational,
 }
 
+/// The contents of a unit bracket, `[1e3 kg m/s^2]` has a factor of 1000.
+#[derive(Debug, Clone)]
+pub struct UnitExpr {
+    pub factor: f64,
+    pub terms: Vec<UnitTerm>,
+}
+
 #[derive(Clone, Debug)]
 pub enum UnaryOp {
     Negative,
-    Units(Vec<UnitTerm>),
+    Not,
+    Units(UnitExpr),
+    Convert(UnitExpr),
+    /// `-> [h:min:s]` or `-> [h] [min] [s]`, from the largest unit to the smallest
+    ConvertMixed(Vec<UnitExpr>),
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +75,11 @@ pub enum NodeContent {
     Function(String, Vec<Node>),
     Value(Value),
     Variable(String),
+    /// `value ± sigma`, an independent source of uncertainty
+    Uncertain(Box<Node>, Box<Node>, UncertaintySource),
+    /// `if(condition, a, b)`, only the chosen value is evaluated unless the condition is a
+    /// vector mask
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
 }
 
 #[derive(Debug, Clone)]
@@ -46,13 +89,68 @@ pub struct Node {
     pub end: usize,
 }
 
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Expression(Node),
+    /// `a = 2 b`, the span is that of the name
+    ConstDefinition {
+        name: String,
+        value: Node,
+        start: usize,
+        end: usize,
+    },
+    /// `ke(m [kg], v [m/s]) [J] = m v² / 2`, the span is that of the name
+    FunctionDefinition {
+        name: String,
+        evaluator: Evaluator,
+        start: usize,
+        end: usize,
+    },
+    /// `unit furlong = 201.168 [m]`, the span is that of the name
+    UnitDeclaration {
+        name: String,
+        value: Node,
+        start: usize,
+        end: usize,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub enum NodeErrorContent {
     ValueError(ValueError),
-    VarNameError(String),
-    FuncNameError(String),
-    UnitNameError(String),
+    /// Unknown names are given along with the most similar known names, best first
+    VarNameError(String, Vec<String>),
+    FuncNameError(String, Vec<String>),
+    UnitNameError(String, Vec<String>),
+    MisplacedLogUnit(String),
+    UnitExistsError(String),
+    NotPlainUnitValue,
+    NegativeUnitValue,
+    MixedUnitsOfVector,
+    /// The units to split a value into are not each smaller than the one before
+    UnorderedMixedUnits,
     ParamCountError(usize, usize),
+    /// The limit that ran out along with the user functions being called at the time,
+    /// outermost first
+    CallLimitError(CallLimit, Vec<String>),
+    /// A user function which always calls itself, through the functions in between
+    UnboundedRecursion(Vec<String>),
+    /// Every number for a source of uncertainty has been given out by the registry
+    SourceLimitError,
+    /// A formula which would use itself, through the constants in between
+    CyclicConstError(Vec<String>),
+    /// The formula of the named constant failed after a constant it uses was redefined
+    FormulaError(String, Box<NodeError>),
+    /// Every dimension mismatch found in the body of a user function as it was defined,
+    /// along with the dimensions they are described with, which name its dimension
+    /// variables
+    DimensionErrors(Vec<NodeError>, DimensionTable),
+    /// The argument for the named parameter does not have the declared dimension, which is
+    /// given first
+    ParamDimensionError(String, SIDimension, SIDimension),
+    /// A dimension variable which no parameter before it determines, as in `f(x [X Y])`
+    /// or `f(x) [D]`
+    UnboundDimensionVariable(String),
     NestedError(String, Box<EvaluationError>),
 }
 
@@ -63,11 +161,30 @@ pub struct NodeError {
     pub end: usize,
 }
 
+/// A dimension declared for a parameter or the result of a user function, such as `[m/s]`.
+///
+/// Names which are not units are dimension variables, standing for whatever the arguments
+/// make them. `sq(x [D]) [D^2]` takes x of any dimension, while `f(x [D], y [D])` needs x
+/// and y to have the same one. A name like a misspelt unit, such as `kgg`, is an unknown
+/// unit instead, and a leading `'` as in `'D` makes any name a variable. Only the dimension
+/// matters, so `[km]` and `[m]` are the same declaration.
+#[derive(Debug, Clone)]
+pub struct DimensionAnnotation {
+    pub units: UnitExpr,
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Evaluator {
     pub parent: Node,
     pub evalstr: String,
     pub params: Vec<String>,
+    /// Declared dimensions of the parameters, `None` for those which take any value
+    pub param_dims: Vec<Option<DimensionAnnotation>>,
+    pub result_dim: Option<DimensionAnnotation>,
+    /// The declarations resolved by `Environment::define_function`
+    pub(crate) signature: Signature,
 }
 
 #[derive(Debug, Clone)]
@@ -84,21 +201,127 @@ pub struct ConversionValue {
     pub dim: SIDimension,
 }
 
+/// Whether plane and solid angles are tracked as dimensions of their own.
+///
+/// Angle units always carry an angle exponent in the unit table, it is stripped on lookup
+/// unless the mode is `Strict`. In strict mode adding rad/s to Hz is a dimension error and
+/// inverse trigonometric functions return radians.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum AngleMode {
+    #[default]
+    Dimensionless,
+    Strict,
+}
+
+/// A logarithmic unit, where x of the unit is `reference * base^(x / scale)` in SI units.
+///
+/// Decibels of power quantities have a scale of 10 and those of field quantities a scale
+/// of 20.
+#[derive(Debug, Clone)]
+pub struct LogConversion {
+    pub reference: f64,
+    pub dim: SIDimension,
+    pub base: f64,
+    pub scale: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum CallLimit {
+    Depth(usize),
+    Fuel(usize),
+}
+
+/// Limits on evaluating an expression, so that runaway recursion in user functions gives
+/// an error rather than overflowing the stack.
+#[derive(Copy, Clone, Debug)]
+pub struct EvalLimits {
+    /// Most user function calls in progress at once
+    pub max_depth: usize,
+    /// Most nodes evaluated, counting those in the bodies of user functions
+    pub fuel: usize,
+}
+
+/// A constant defined by an expression, kept up to date as the constants it uses change.
+#[derive(Debug, Clone)]
+pub struct Formula {
+    pub node: Node,
+    /// Constants used by the expression, including through the user functions it calls
+    pub dependencies: Vec<String>,
+    /// Functions called by the expression or the user functions it calls, in name order
+    pub functions: Vec<String>,
+    pub value: Result<Value, NodeError>,
+}
+
+/// A definition which hides a name that is easy to rely on without noticing.
+#[derive(Debug, Clone)]
+pub enum ShadowWarning {
+    /// A built-in constant or function such as `pi` or `sin`
+    Builtin(String),
+    /// A name from the global defaults at the root of the scopes
+    Default(String),
+}
+
+/// Names of the sources of uncertainty of values, numbered in the order they are first
+/// evaluated so that derivatives can be kept by number. An environment shares its registry
+/// with the scopes it encloses, and the numbers mean nothing to any other registry.
+#[derive(Debug, Default)]
+pub struct SourceRegistry {
+    names: RwLock<Vec<String>>,
+}
+
+/// The source of a `±` as parsed. Copies of the node share it, so every evaluation of the
+/// node depends on the same source, which is registered when first evaluated.
+#[derive(Debug, Clone)]
+pub struct UncertaintySource(Arc<SourceSlot>);
+
+#[derive(Debug)]
+struct SourceSlot {
+    name: String,
+    // The number given by the first registry to see the source, then those of any others.
+    // A registry is told apart by its address, which is not reused while it is held here.
+    first: OnceLock<(Weak<SourceRegistry>, SourceId)>,
+    others: Mutex<Vec<(Weak<SourceRegistry>, SourceId)>>,
+}
+
+/// Units, constants and functions in scope, as one layer of a stack such as global
+/// defaults, a user session and a loaded file. Names are looked up from the innermost
+/// scope outwards through `parent`.
 #[derive(Debug)]
 pub struct Environment {
     pub consts: HashMap<String, Value>,
-    pub evaluators: HashMap<String, Evaluator>,
+    pub formulas: HashMap<String, Formula>,
+    pub evaluators: HashMap<String, Arc<Evaluator>>,
     pub units: HashMap<String, ConversionValue>,
+    pub prefixes: HashMap<String, f64>,
+    pub log_units: HashMap<String, LogConversion>,
+    pub dimensions: DimensionTable,
+    pub angle_mode: AngleMode,
+    pub limits: EvalLimits,
+    pub parallelism: Parallelism,
+    /// Shared with the enclosing scopes, so that values from any of them can be combined
+    pub sources: Arc<SourceRegistry>,
+    pub parent: Option<Arc<Environment>>,
 }
 
 impl Evaluator {
     pub fn eval(&self, env: &Environment, params: &[Value]) -> Result<Value, EvaluationError> {
+        with_parallelism(env.parallelism, || {
+            self.eval_within(env, params, &mut CallBudget::new(&env.limits))
+        })
+    }
+
+    fn eval_within(
+        &self,
+        env: &Environment,
+        params: &[Value],
+        budget: &mut CallBudget,
+    ) -> Result<Value, EvaluationError> {
         // it is assumed that the amount of variables passed is correct
         let labelled_params =
             HashMap::from_iter(self.params.iter().cloned().zip(params.iter().cloned()));
 
         self.parent
-            .eval(env, &labelled_params)
+            .eval_within(env, &labelled_params, budget)
             .map_err(|e| e.to_evalerr(&self.evalstr))
     }
 }
@@ -1,119 +1,458 @@
 use crate::{
     eval::{
-        BinaryOp, Environment, Node, NodeContent, NodeError, NodeErrorContent, UnaryOp, UnitTerm,
+        AngleMode, BinaryOp, CallBudget, ConversionValue, Environment, EvaluationError, Evaluator,
+        LogConversion, Node, NodeContent, NodeError, NodeErrorContent, UnaryOp, UncertaintySource,
+        UnitExpr, UnitTerm,
+        builtin::{get_builtin, get_builtin_const, returns_angle},
+        repr::units_repr,
+        units::{decompose, radian},
     },
-    f64plus::FloatPlus,
-    value::{Quantity, SIDimension, Value},
+    f64plus::{FloatPlus, with_parallelism},
+    rational::Rational,
+    value::{Derivatives, MixedAmounts, Quantity, SIDimension, Value, ValueError},
 };
 use std::collections::HashMap;
 
+// Evaluates a child of a node. Both nodes and their compiled form go through the functions
+// below, which keeps the two in agreement about everything but how children are reached.
+pub(super) type Operand<'a> = dyn FnMut(&mut CallBudget) -> Result<Value, NodeError> + 'a;
+pub(super) type Operands<'a> = dyn FnMut(&mut CallBudget) -> Result<Vec<Value>, NodeError> + 'a;
+
+fn operand<'a>(
+    node: &'a Node,
+    env: &'a Environment,
+    params: &'a HashMap<String, Value>,
+) -> impl FnMut(&mut CallBudget) -> Result<Value, NodeError> + 'a {
+    move |budget| node.eval_within(env, params, budget)
+}
+
 impl Node {
+    /// Evaluates the node within the call limits of the environment.
     pub fn eval(
         &self,
         env: &Environment,
         params: &HashMap<String, Value>,
     ) -> Result<Value, NodeError> {
+        with_parallelism(env.parallelism, || {
+            self.eval_within(env, params, &mut CallBudget::new(&env.limits))
+        })
+    }
+
+    pub(super) fn eval_within(
+        &self,
+        env: &Environment,
+        params: &HashMap<String, Value>,
+        budget: &mut CallBudget,
+    ) -> Result<Value, NodeError> {
+        budget.use_fuel(self.start, self.end)?;
+        let (start, end) = (self.start, self.end);
+        let operand = |node| operand(node, env, params);
+
         match &self.content {
-            NodeContent::Binary(lhs, op, rhs) => {
-                eval_binary(lhs, rhs, *op, env, params, self.start, self.end)
-            }
-            NodeContent::Unary(op, operand) => {
-                eval_unary(operand, op, env, params, self.start, self.end)
+            NodeContent::Binary(lhs, op, rhs) => eval_binary(
+                &mut operand(lhs),
+                &mut operand(rhs),
+                *op,
+                budget,
+                start,
+                end,
+            ),
+            NodeContent::Unary(op, operand_node) => {
+                eval_unary(&mut operand(operand_node), op, env, budget, start, end)
             }
             NodeContent::Function(func, param_nodes) => {
-                eval_func(func, param_nodes, env, params, self.start, self.end)
+                let mut eval_args = |budget: &mut CallBudget| {
+                    param_nodes
+                        .iter()
+                        .map(|node| node.eval_within(env, params, budget))
+                        .collect()
+                };
+                eval_func(func, param_nodes, &mut eval_args, env, budget, start, end)
             }
             NodeContent::Value(val) => Ok(val.clone()),
-            NodeContent::Variable(var) => eval_var(var, env, params, self.start, self.end),
+            NodeContent::Variable(var) => match params.get(var) {
+                Some(value) => Ok(value.clone()),
+                None => eval_const(var, env, start, end)
+                    .unwrap_or_else(|| Err(unknown_var(var, env, params, start, end))),
+            },
+            NodeContent::Uncertain(value, sigma, source) => eval_uncertain(
+                &mut operand(value),
+                &mut operand(sigma),
+                source,
+                env,
+                budget,
+                start,
+                end,
+            ),
+            NodeContent::Conditional(condition, if_true, if_false) => eval_conditional(
+                &mut operand(condition),
+                &mut operand(if_true),
+                &mut operand(if_false),
+                budget,
+                start,
+                end,
+            ),
         }
     }
 }
 
-fn eval_func(
+pub(super) fn eval_func(
     func: &str,
     param_nodes: &[Node],
+    eval_args: &mut Operands,
     env: &Environment,
-    params: &HashMap<String, Value>,
+    budget: &mut CallBudget,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    let evaluator = match env.get_evaluator(func) {
+        Some(evaluator) => evaluator,
+        None => return eval_builtin_func(func, param_nodes, eval_args, env, budget, start, end),
+    };
+
+    let param_values = user_func_args(evaluator, param_nodes, eval_args, budget, start, end)?;
+    budget.enter(func, start, end)?;
+    let result = evaluator.eval_within(env, &param_values, budget);
+    budget.leave();
+
+    user_func_result(func, result, start, end)
+}
+
+// Arguments of a call of a user function, once there are as many as it has parameters and
+// they have their declared dimensions
+pub(super) fn user_func_args(
+    evaluator: &Evaluator,
+    param_nodes: &[Node],
+    eval_args: &mut Operands,
+    budget: &mut CallBudget,
+    start: usize,
+    end: usize,
+) -> Result<Vec<Value>, NodeError> {
+    if evaluator.params.len() != param_nodes.len() {
+        return Err(NodeError {
+            content: NodeErrorContent::ParamCountError(evaluator.params.len(), param_nodes.len()),
+            start,
+            end,
+        });
+    }
+
+    let param_values = eval_args(budget)?;
+    evaluator.check_arguments(&param_values, param_nodes)?;
+    Ok(param_values)
+}
+
+pub(super) fn user_func_result(
+    func: &str,
+    result: Result<Value, EvaluationError>,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    result.map_err(|e| match e.content {
+        // Running out is reported once at the outermost call rather than nested per call
+        NodeErrorContent::CallLimitError(..) => NodeError {
+            content: e.content,
+            start,
+            end,
+        },
+        _ => NodeError {
+            content: NodeErrorContent::NestedError(func.into(), Box::new(e)),
+            start,
+            end,
+        },
+    })
+}
+
+pub(super) fn eval_builtin_func(
+    func: &str,
+    param_nodes: &[Node],
+    eval_args: &mut Operands,
+    env: &Environment,
+    budget: &mut CallBudget,
     start: usize,
     end: usize,
 ) -> Result<Value, NodeError> {
-    let evaluator = env.evaluators.get(func).ok_or_else(|| NodeError {
-        content: NodeErrorContent::FuncNameError(func.into()),
+    let builtin = get_builtin(func).ok_or_else(|| NodeError {
+        content: NodeErrorContent::FuncNameError(func.into(), env.suggest_funcs(func)),
         start,
         end,
     })?;
 
-    if evaluator.params.len() != param_nodes.len() {
+    if builtin.param_count() != param_nodes.len() {
         return Err(NodeError {
-            content: NodeErrorContent::ParamCountError(evaluator.params.len(), param_nodes.len()),
+            content: NodeErrorContent::ParamCountError(builtin.param_count(), param_nodes.len()),
             start,
             end,
         });
     }
 
-    let param_values = param_nodes
-        .iter()
-        .map(|node| node.eval(env, params))
-        .collect::<Result<Vec<Value>, NodeError>>()?;
+    let param_values = eval_args(budget)?;
+
+    let result = builtin.call(&param_values);
+    let result = if env.angle_mode == AngleMode::Strict && returns_angle(func) {
+        result.and_then(|v| v.mul(&radian().into()))
+    } else {
+        result
+    };
 
-    evaluator.eval(env, &param_values).map_err(|e| NodeError {
-        content: NodeErrorContent::NestedError(func.into(), Box::new(e)),
+    result.map_err(|e| NodeError {
+        content: NodeErrorContent::ValueError(e),
         start,
         end,
     })
 }
 
-fn eval_unary(
-    operand: &Node,
+pub(super) fn eval_unary(
+    operand: &mut Operand,
     op: &UnaryOp,
     env: &Environment,
-    params: &HashMap<String, Value>,
+    budget: &mut CallBudget,
     start: usize,
     end: usize,
 ) -> Result<Value, NodeError> {
-    let operand_value = operand.eval(env, params)?;
-    match op {
-        UnaryOp::Negative => Ok(operand_value.negative()),
+    let operand_value = operand(budget)?;
+    let result = match op {
+        UnaryOp::Negative => return Ok(operand_value.negative()),
+        UnaryOp::Not => operand_value.not(),
         UnaryOp::Units(units) => {
-            let conversion = eval_unit_factors(&units, env)?;
-            Ok(operand_value.mul(&conversion.into()).unwrap())
+            let (log_unit, linear_terms) = split_log_unit(units, env)?;
+            let conversion = eval_unit_factors(units.factor, linear_terms, env)?;
+
+            match log_unit {
+                Some(log_unit) => log_unit.to_linear(&operand_value),
+                None => Ok(operand_value),
+            }
+            .and_then(|v| v.mul(&conversion.into()))
+        }
+        UnaryOp::Convert(units) => {
+            let (log_unit, linear_terms) = split_log_unit(units, env)?;
+            let conversion = eval_unit_factors(units.factor, linear_terms, env)?;
+            let conversion_dim = conversion.dim.clone();
+
+            operand_value
+                .div(&conversion.into())
+                .and_then(|ratio| match log_unit {
+                    Some(log_unit) => log_unit.from_linear(&ratio),
+                    None if ratio.dimless() => Ok(ratio),
+                    None => Err(ValueError::UnequalDimensions(
+                        operand_value.dim(),
+                        conversion_dim,
+                    )),
+                })
         }
+        UnaryOp::ConvertMixed(unit_groups) => {
+            return eval_convert_mixed(&operand_value, unit_groups, env, start, end);
+        }
+    };
+
+    result.map_err(|e| NodeError {
+        content: NodeErrorContent::ValueError(e),
+        start,
+        end,
+    })
+}
+
+// A logarithmic unit may only be the first term with a power of one, any following
+// terms scale its reference like in [dBm/Hz]
+pub(super) fn split_log_unit<'a, 'b>(
+    units: &'a UnitExpr,
+    env: &'b Environment,
+) -> Result<(Option<&'b LogConversion>, &'a [UnitTerm]), NodeError> {
+    for (i, term) in units.terms.iter().enumerate() {
+        if env.get_unit(&term.unit).is_some() {
+            continue;
+        }
+
+        let log_unit = match env.get_log_unit(&term.unit) {
+            Some(log_unit) => log_unit,
+            None => continue,
+        };
+
+        if i != 0 || term.power != Rational::ONE {
+            return Err(NodeError {
+                content: NodeErrorContent::MisplacedLogUnit(term.unit.clone()),
+                start: term.start,
+                end: term.end,
+            });
+        }
+
+        return Ok((Some(log_unit), &units.terms[1..]));
     }
+
+    Ok((None, &units.terms))
 }
 
-fn eval_unit_factors(units: &[UnitTerm], env: &Environment) -> Result<Quantity, NodeError> {
-    let mut result_factor = 1.0;
+// The value is returned along with the amount of each unit. All but the last amount are
+// whole numbers, so only the last one carries derivatives.
+fn eval_convert_mixed(
+    operand_value: &Value,
+    unit_groups: &[UnitExpr],
+    env: &Environment,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    let value_error = |e| NodeError {
+        content: NodeErrorContent::ValueError(e),
+        start,
+        end,
+    };
+
+    let quantity = operand_value
+        .try_promote_quantity()
+        .ok_or_else(|| value_error(ValueError::UnsupportedComplexOperand))?;
+    let total = match quantity.value {
+        FloatPlus::Scalar(x) => x,
+        FloatPlus::Vector(_) => {
+            return Err(NodeError {
+                content: NodeErrorContent::MixedUnitsOfVector,
+                start,
+                end,
+            });
+        }
+    };
+
+    let mut factors = Vec::new();
+    for units in unit_groups {
+        if let (Some(_), _) = split_log_unit(units, env)? {
+            let log_term = &units.terms[0];
+            return Err(NodeError {
+                content: NodeErrorContent::MisplacedLogUnit(log_term.unit.clone()),
+                start: log_term.start,
+                end: log_term.end,
+            });
+        }
+
+        let conversion = eval_unit_conversion(units.factor, &units.terms, env)?;
+        if conversion.dim != quantity.dim {
+            return Err(value_error(ValueError::UnequalDimensions(
+                quantity.dim,
+                conversion.dim,
+            )));
+        }
+        factors.push(conversion.factor);
+    }
+    if factors.windows(2).any(|pair| pair[0] <= pair[1]) {
+        return Err(NodeError {
+            content: NodeErrorContent::UnorderedMixedUnits,
+            start,
+            end,
+        });
+    }
+
+    let amounts = decompose(total, &factors);
+    let last = amounts.len().checked_sub(1);
+    let parts = amounts
+        .into_iter()
+        .zip(unit_groups)
+        .enumerate()
+        .map(|(i, (amount, units))| {
+            let mut amount = Quantity::from_float(amount);
+            if Some(i) == last {
+                let last_factor = FloatPlus::Scalar(factors[i]);
+                amount.derivatives = quantity.derivatives.map(|drv| drv.div(&last_factor));
+            }
+            (amount, units_repr(units))
+        })
+        .collect();
+
+    Ok(Value::Mixed(MixedAmounts { quantity, parts }))
+}
+
+pub(super) fn eval_unit_factors(
+    factor: f64,
+    terms: &[UnitTerm],
+    env: &Environment,
+) -> Result<Quantity, NodeError> {
+    let conversion = eval_unit_conversion(factor, terms, env)?;
+
+    Ok(Quantity {
+        value: FloatPlus::Scalar(conversion.factor),
+        derivatives: Derivatives::new(),
+        dim: conversion.dim,
+    })
+}
+
+pub(super) fn eval_unit_conversion(
+    factor: f64,
+    terms: &[UnitTerm],
+    env: &Environment,
+) -> Result<ConversionValue, NodeError> {
+    let mut result_factor = factor;
     let mut result_dim = SIDimension::DIMLESS;
 
-    for term in units {
-        let conversion = env.units.get(&term.unit).ok_or_else(|| NodeError {
-            content: NodeErrorContent::UnitNameError(term.unit.clone()),
+    for term in terms {
+        let conversion = env.resolve_unit(&term.unit).ok_or_else(|| NodeError {
+            content: NodeErrorContent::UnitNameError(
+                term.unit.clone(),
+                env.suggest_units(&term.unit),
+            ),
             start: term.start,
             end: term.end,
         })?;
 
+        let dim = match env.angle_mode {
+            AngleMode::Dimensionless => conversion.dim.without_angles(),
+            AngleMode::Strict => conversion.dim,
+        };
+
         result_factor *= conversion.factor.powf(term.power.into());
-        result_dim = result_dim.mul(&conversion.dim.pow(term.power));
+        result_dim = result_dim.mul(&dim.pow(term.power));
     }
 
-    Ok(Quantity {
-        value: FloatPlus::Scalar(result_factor),
-        derivatives: HashMap::new(),
+    Ok(ConversionValue {
+        factor: result_factor,
         dim: result_dim,
     })
 }
 
-fn eval_binary(
-    lhs: &Node,
-    rhs: &Node,
-    op: BinaryOp,
+pub(super) fn eval_uncertain(
+    value: &mut Operand,
+    sigma: &mut Operand,
+    source: &UncertaintySource,
     env: &Environment,
-    params: &HashMap<String, Value>,
+    budget: &mut CallBudget,
     start: usize,
     end: usize,
 ) -> Result<Value, NodeError> {
-    let left = lhs.eval(env, params)?;
-    let right = rhs.eval(env, params)?;
+    let value = value(budget)?;
+    let sigma = sigma(budget)?;
+    let source = source.id(&env.sources).ok_or(NodeError {
+        content: NodeErrorContent::SourceLimitError,
+        start,
+        end,
+    })?;
+    value.plus_minus(&sigma, source).map_err(|e| NodeError {
+        content: NodeErrorContent::ValueError(e),
+        start,
+        end,
+    })
+}
+
+pub(super) fn eval_binary(
+    lhs: &mut Operand,
+    rhs: &mut Operand,
+    op: BinaryOp,
+    budget: &mut CallBudget,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    let to_node_error = |e| NodeError {
+        content: NodeErrorContent::ValueError(e),
+        start,
+        end,
+    };
+    let left = lhs(budget)?;
+
+    // A scalar left side may already decide the result, so in `x != 0 and 1/x > 2` the
+    // division is never evaluated when x is zero
+    if matches!(op, BinaryOp::And | BinaryOp::Or) {
+        match (op, left.scalar_truth().map_err(to_node_error)?) {
+            (BinaryOp::And, Some(false)) => return Ok(Value::boolean(false)),
+            (BinaryOp::Or, Some(true)) => return Ok(Value::boolean(true)),
+            _ => (),
+        }
+    }
+
+    let right = rhs(budget)?;
 
     match op {
         BinaryOp::Add => left.add(&right),
@@ -121,29 +460,70 @@ fn eval_binary(
         BinaryOp::Mul => left.mul(&right),
         BinaryOp::Div => left.div(&right),
         BinaryOp::Pow => left.pow(&right),
+        BinaryOp::Compare(comparison) => left.compare(&right, comparison),
+        BinaryOp::And => left.and(&right),
+        BinaryOp::Or => left.or(&right),
     }
-    .map_err(|e| NodeError {
+    .map_err(to_node_error)
+}
+
+pub(super) fn eval_conditional(
+    condition: &mut Operand,
+    if_true: &mut Operand,
+    if_false: &mut Operand,
+    budget: &mut CallBudget,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    let to_node_error = |e| NodeError {
         content: NodeErrorContent::ValueError(e),
         start,
         end,
-    })
+    };
+    let condition_value = condition(budget)?;
+
+    match condition_value.scalar_truth().map_err(to_node_error)? {
+        Some(true) => if_true(budget),
+        Some(false) => if_false(budget),
+        None => {
+            let true_value = if_true(budget)?;
+            let false_value = if_false(budget)?;
+            condition_value
+                .select(&true_value, &false_value)
+                .map_err(to_node_error)
+        }
+    }
 }
 
-fn eval_var(
+/// The value of a constant, or `None` if there is no constant of that name. Parameters
+/// are looked up by the caller, as they hide constants.
+pub(super) fn eval_const(
     var: &str,
     env: &Environment,
-    params: &HashMap<String, Value>,
     start: usize,
     end: usize,
-) -> Result<Value, NodeError> {
-    let result = params.get(var).or_else(|| env.consts.get(var));
-
-    match result {
-        Some(v) => Ok(v.clone()),
-        None => Err(NodeError {
-            content: NodeErrorContent::VarNameError(var.into()),
+) -> Option<Result<Value, NodeError>> {
+    match env.lookup_const(var) {
+        Some(Ok(v)) => Some(Ok(v.clone())),
+        Some(Err(e)) => Some(Err(NodeError {
+            content: NodeErrorContent::FormulaError(var.into(), Box::new(e.clone())),
             start,
             end,
-        }),
+        })),
+        None => get_builtin_const(var).map(Ok),
+    }
+}
+
+pub(super) fn unknown_var(
+    var: &str,
+    env: &Environment,
+    params: &HashMap<String, Value>,
+    start: usize,
+    end: usize,
+) -> NodeError {
+    NodeError {
+        content: NodeErrorContent::VarNameError(var.into(), env.suggest_vars(var, params)),
+        start,
+        end,
     }
 }
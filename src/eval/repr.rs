@@ -0,0 +1,197 @@
+use super::{BinaryOp, Node, NodeContent, UnaryOp, UnitExpr};
+use crate::{
+    f64plus::FloatPlus,
+    value::{Comparison, DimensionTable, Quantity, SIDimension, Value},
+};
+
+fn binary_op_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Pow => "^",
+        BinaryOp::Compare(Comparison::Less) => "<",
+        BinaryOp::Compare(Comparison::LessEqual) => "<=",
+        BinaryOp::Compare(Comparison::Greater) => ">",
+        BinaryOp::Compare(Comparison::GreaterEqual) => ">=",
+        BinaryOp::Compare(Comparison::Equal) => "==",
+        BinaryOp::Compare(Comparison::NotEqual) => "!=",
+        BinaryOp::Compare(Comparison::Approx) => "~=",
+        BinaryOp::And => "and",
+        BinaryOp::Or => "or",
+    }
+}
+
+// Expressions only have superscripts and '√' for powers, so `(x ^ 2)` would not parse again
+fn superscript(n: i32) -> String {
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    let digits = n
+        .unsigned_abs()
+        .to_string()
+        .bytes()
+        .map(|digit| DIGITS[(digit - b'0') as usize])
+        .collect::<String>();
+    if n < 0 {
+        format!("⁻{digits}")
+    } else {
+        digits
+    }
+}
+
+// Powers come from superscripts and '√', so their exponents are integers or halves. Any
+// other would have been built by hand and is written with '^'.
+fn power_repr(base: &Node, exponent: &Node, dims: &DimensionTable) -> String {
+    let exponent = match exponent.content {
+        NodeContent::Value(Value::Rational(r)) => r,
+        _ => {
+            return format!("({} ^ {})", base.repr_with(dims), exponent.repr_with(dims));
+        }
+    };
+
+    match exponent.denominator {
+        1 => format!(
+            "({}{})",
+            base.repr_with(dims),
+            superscript(exponent.numerator)
+        ),
+        2 if exponent.numerator == 1 => format!("(√{})", base.repr_with(dims)),
+        2 => format!(
+            "((√{}){})",
+            base.repr_with(dims),
+            superscript(exponent.numerator)
+        ),
+        _ => format!("({} ^ {exponent})", base.repr_with(dims)),
+    }
+}
+
+fn floats_repr(x: &FloatPlus) -> String {
+    match x {
+        FloatPlus::Scalar(s) => s.to_string(),
+        FloatPlus::Vector(v) => {
+            let elements = v.iter().map(f64::to_string).collect::<Vec<String>>();
+            format!("{{{}}}", elements.join(", "))
+        }
+    }
+}
+
+fn with_dim(magnitude: String, dim: &SIDimension, dims: &DimensionTable) -> String {
+    if *dim == SIDimension::DIMLESS {
+        magnitude
+    } else {
+        format!("{magnitude} [{}]", dims.format(dim))
+    }
+}
+
+// Quantities are written in SI units, with the combined uncertainty if there is any
+fn quantity_repr(q: &Quantity, dims: &DimensionTable) -> String {
+    if q.derivatives.is_empty() {
+        return with_dim(floats_repr(&q.value), &q.dim, dims);
+    }
+
+    let mut sigma = FloatPlus::ZERO;
+    for drv in q.derivatives.values() {
+        sigma.apply_binary_func_in_place(drv, |var, d| var + d * d);
+    }
+    sigma.apply_func_in_place(f64::sqrt);
+    let magnitude = format!("({} ± {})", floats_repr(&q.value), floats_repr(&sigma));
+    with_dim(magnitude, &q.dim, dims)
+}
+
+fn value_repr(value: &Value, dims: &DimensionTable) -> String {
+    match value {
+        Value::Rational(r) if r.denominator == 1 => r.to_string(),
+        Value::Rational(r) => format!("({r})"),
+        Value::Quantity(q) => quantity_repr(q, dims),
+        Value::Complex(c) => {
+            let magnitude = format!("({} + {} i)", floats_repr(&c.real), floats_repr(&c.imag));
+            with_dim(magnitude, &c.dim, dims)
+        }
+        // Juxtaposed amounts with units are summed again when parsed
+        Value::Mixed(m) => {
+            let parts = m
+                .parts
+                .iter()
+                .map(|(amount, units)| format!("{} {units}", quantity_repr(amount, dims)))
+                .collect::<Vec<String>>();
+            format!("({})", parts.join(" "))
+        }
+    }
+}
+
+pub(super) fn units_repr(units: &UnitExpr) -> String {
+    let mut parts = Vec::new();
+    if units.factor != 1. {
+        parts.push(units.factor.to_string());
+    }
+    for term in &units.terms {
+        if term.power == 1.into() {
+            parts.push(term.unit.clone());
+        } else if term.power.is_integral() {
+            parts.push(format!(
+                "{}{}",
+                term.unit,
+                superscript(term.power.numerator)
+            ));
+        } else {
+            parts.push(format!("{}^({})", term.unit, term.power));
+        }
+    }
+    format!("[{}]", parts.join(" "))
+}
+
+impl Node {
+    /// The node written out as an expression, with every operation in parentheses so that
+    /// the structure is plain to see.
+    pub fn repr(&self) -> String {
+        self.repr_with(&DimensionTable::default())
+    }
+
+    /// As `repr`, with the dimensions of values named as in `dims`. Those of an environment
+    /// name the dimensions registered at runtime, so that the values can be parsed again.
+    pub fn repr_with(&self, dims: &DimensionTable) -> String {
+        match &self.content {
+            NodeContent::Value(value) => value_repr(value, dims),
+            NodeContent::Variable(var) => var.clone(),
+            NodeContent::Binary(base, BinaryOp::Pow, exponent) => power_repr(base, exponent, dims),
+            NodeContent::Binary(lhs, op, rhs) => {
+                format!(
+                    "({} {} {})",
+                    lhs.repr_with(dims),
+                    binary_op_symbol(*op),
+                    rhs.repr_with(dims)
+                )
+            }
+            NodeContent::Unary(op, operand) => match op {
+                UnaryOp::Negative => format!("-{}", operand.repr_with(dims)),
+                UnaryOp::Not => format!("(not {})", operand.repr_with(dims)),
+                UnaryOp::Units(units) => {
+                    format!("({} {})", operand.repr_with(dims), units_repr(units))
+                }
+                UnaryOp::Convert(units) => {
+                    format!("({} -> {})", operand.repr_with(dims), units_repr(units))
+                }
+                UnaryOp::ConvertMixed(unit_groups) => {
+                    let groups = unit_groups.iter().map(units_repr).collect::<Vec<String>>();
+                    format!("({} -> {})", operand.repr_with(dims), groups.join(" "))
+                }
+            },
+            NodeContent::Function(func, param_nodes) => {
+                let params = param_nodes
+                    .iter()
+                    .map(|param| param.repr_with(dims))
+                    .collect::<Vec<String>>();
+                format!("{func}({})", params.join(", "))
+            }
+            NodeContent::Uncertain(value, sigma, _) => {
+                format!("({} ± {})", value.repr_with(dims), sigma.repr_with(dims))
+            }
+            NodeContent::Conditional(condition, if_true, if_false) => format!(
+                "if({}, {}, {})",
+                condition.repr_with(dims),
+                if_true.repr_with(dims),
+                if_false.repr_with(dims)
+            ),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use super::{
+    ConversionValue, Environment, Evaluator, Formula, LogConversion, NodeError, ShadowWarning,
+    builtin::{get_builtin, get_builtin_const},
+};
+use crate::value::{SourceId, Value};
+use std::{collections::HashMap, sync::Arc};
+
+impl Environment {
+    /// An empty scope on top of `parent`, which sees all of its names until they are
+    /// defined again here.
+    ///
+    /// The parent is shared rather than copied, so one read-only base environment can back
+    /// any number of sessions.
+    pub fn child(parent: Arc<Environment>) -> Self {
+        Environment {
+            consts: HashMap::new(),
+            formulas: HashMap::new(),
+            evaluators: HashMap::new(),
+            units: HashMap::new(),
+            prefixes: HashMap::new(),
+            log_units: HashMap::new(),
+            dimensions: parent.dimensions.clone(),
+            angle_mode: parent.angle_mode,
+            limits: parent.limits,
+            parallelism: parent.parallelism,
+            sources: parent.sources.clone(),
+            parent: Some(parent),
+        }
+    }
+
+    /// This scope followed by its enclosing scopes, out to the global defaults.
+    pub fn scopes(&self) -> impl Iterator<Item = &Environment> {
+        std::iter::successors(Some(self), |env| env.parent.as_deref())
+    }
+
+    fn root(&self) -> &Environment {
+        self.scopes().last().unwrap()
+    }
+
+    // Entries of the table in any scope, where those of inner scopes hide outer ones
+    fn visible<'a, T>(
+        &'a self,
+        table: impl Fn(&'a Environment) -> &'a HashMap<String, T>,
+    ) -> HashMap<&'a str, &'a T> {
+        let mut entries = HashMap::new();
+        for env in self.scopes() {
+            for (name, entry) in table(env) {
+                entries.entry(name.as_str()).or_insert(entry);
+            }
+        }
+        entries
+    }
+
+    /// The value of the constant `name`, or the error of evaluating its formula.
+    pub fn lookup_const(&self, name: &str) -> Option<Result<&Value, &NodeError>> {
+        self.scopes().find_map(|env| match env.consts.get(name) {
+            Some(value) => Some(Ok(value)),
+            None => env.formulas.get(name).map(|formula| formula.value.as_ref()),
+        })
+    }
+
+    pub fn get_const(&self, name: &str) -> Option<&Value> {
+        self.lookup_const(name)?.ok()
+    }
+
+    /// How the source of uncertainty `id` was written, as numbered in this environment.
+    pub fn source_name(&self, id: SourceId) -> Option<String> {
+        self.sources.name(id)
+    }
+
+    pub fn get_evaluator(&self, name: &str) -> Option<&Evaluator> {
+        self.shared_evaluator(name).map(Arc::as_ref)
+    }
+
+    // Compiled nodes keep the evaluators they compiled, to know when one is replaced
+    pub(super) fn shared_evaluator(&self, name: &str) -> Option<&Arc<Evaluator>> {
+        self.scopes().find_map(|env| env.evaluators.get(name))
+    }
+
+    pub fn get_unit(&self, name: &str) -> Option<&ConversionValue> {
+        self.scopes().find_map(|env| env.units.get(name))
+    }
+
+    pub fn get_prefix(&self, name: &str) -> Option<f64> {
+        self.scopes()
+            .find_map(|env| env.prefixes.get(name).copied())
+    }
+
+    pub fn get_log_unit(&self, name: &str) -> Option<&LogConversion> {
+        self.scopes().find_map(|env| env.log_units.get(name))
+    }
+
+    pub fn visible_consts(&self) -> HashMap<&str, &Value> {
+        self.visible(|env| &env.consts)
+    }
+
+    pub fn visible_formulas(&self) -> HashMap<&str, &Formula> {
+        self.visible(|env| &env.formulas)
+    }
+
+    pub fn visible_evaluators(&self) -> HashMap<&str, &Evaluator> {
+        self.visible(|env| &env.evaluators)
+            .into_iter()
+            .map(|(name, evaluator)| (name, evaluator.as_ref()))
+            .collect()
+    }
+
+    pub fn visible_units(&self) -> HashMap<&str, &ConversionValue> {
+        self.visible(|env| &env.units)
+    }
+
+    pub fn visible_prefixes(&self) -> HashMap<&str, &f64> {
+        self.visible(|env| &env.prefixes)
+    }
+
+    pub fn visible_log_units(&self) -> HashMap<&str, &LogConversion> {
+        self.visible(|env| &env.log_units)
+    }
+
+    /// Defines the constant `name` in this scope, warning when it hides a built-in constant
+    /// or one of the global defaults. Formulas of this scope using it are evaluated again.
+    pub fn define_const(&mut self, name: &str, value: Value) -> Option<ShadowWarning> {
+        let warning = self.const_shadow_warning(name);
+        self.formulas.remove(name);
+        self.consts.insert(name.into(), value);
+        self.recompute_dependents(name);
+        warning
+    }
+
+    pub(super) fn const_shadow_warning(&self, name: &str) -> Option<ShadowWarning> {
+        let root = self.root();
+        if get_builtin_const(name).is_some() {
+            Some(ShadowWarning::Builtin(name.into()))
+        } else if self.parent.is_some()
+            && (root.consts.contains_key(name) || root.formulas.contains_key(name))
+        {
+            Some(ShadowWarning::Default(name.into()))
+        } else {
+            None
+        }
+    }
+
+    // Same as for constants, used once a user function is known to be valid
+    pub(super) fn function_shadow_warning(&self, name: &str) -> Option<ShadowWarning> {
+        if get_builtin(name).is_some() {
+            Some(ShadowWarning::Builtin(name.into()))
+        } else if self.parent.is_some() && self.root().evaluators.contains_key(name) {
+            Some(ShadowWarning::Default(name.into()))
+        } else {
+            None
+        }
+    }
+}
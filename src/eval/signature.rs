@@ -0,0 +1,199 @@
+use super::{
+    AngleMode, DimensionAnnotation, Environment, Evaluator, Node, NodeError, NodeErrorContent,
+    UnitTerm,
+};
+use crate::{
+    rational::Rational,
+    value::{SIDimension, Value},
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub(super) struct DimensionVariable {
+    pub name: String,
+    pub power: Rational,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A declared dimension as the product of the dimensions of its units and powers of its
+/// dimension variables.
+#[derive(Debug, Clone)]
+pub(super) struct DimensionPattern {
+    known: SIDimension,
+    variables: Vec<DimensionVariable>,
+}
+
+/// The declared dimensions of a user function with their units looked up, which is done
+/// once when the function is defined, with the units and angle mode of that time. Empty
+/// until then, so nothing is checked.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Signature {
+    pub(super) params: Vec<Option<DimensionPattern>>,
+    pub(super) result: Option<DimensionPattern>,
+}
+
+fn unknown_unit(term: &UnitTerm, similar: Vec<String>) -> NodeError {
+    NodeError {
+        content: NodeErrorContent::UnitNameError(term.unit.clone(), similar),
+        start: term.start,
+        end: term.end,
+    }
+}
+
+impl DimensionPattern {
+    /// Names starting with `'` are dimension variables, as are other names which are not
+    /// units, unless they look like a misspelt unit such as `kgg`. Fails on the first of
+    /// those.
+    pub fn new(annotation: &DimensionAnnotation, env: &Environment) -> Result<Self, NodeError> {
+        let mut known = SIDimension::DIMLESS;
+        let mut variables = Vec::<DimensionVariable>::new();
+
+        for term in &annotation.units.terms {
+            let marked = term.unit.starts_with('\'');
+            if let Some(conversion) = env.resolve_unit(&term.unit).filter(|_| !marked) {
+                let dim = match env.angle_mode {
+                    AngleMode::Dimensionless => conversion.dim.without_angles(),
+                    AngleMode::Strict => conversion.dim,
+                };
+                known = known.mul(&dim.pow(term.power));
+                continue;
+            }
+
+            if !marked {
+                let similar = env.suggest_units(&term.unit);
+                if !similar.is_empty() {
+                    return Err(unknown_unit(term, similar));
+                }
+            }
+            match variables.iter_mut().find(|v| v.name == term.unit) {
+                Some(variable) => variable.power = variable.power + term.power,
+                None => variables.push(DimensionVariable {
+                    name: term.unit.clone(),
+                    power: term.power,
+                    start: term.start,
+                    end: term.end,
+                }),
+            }
+        }
+
+        variables.retain(|v| !v.power.is_zero());
+        Ok(Self { known, variables })
+    }
+
+    pub fn unbound<'a>(
+        &'a self,
+        bindings: &'a HashMap<String, SIDimension>,
+    ) -> impl Iterator<Item = &'a DimensionVariable> {
+        self.variables
+            .iter()
+            .filter(move |v| !bindings.contains_key(&v.name))
+    }
+
+    /// The dimension with the variables filled in, or `None` while any of them is unbound.
+    pub fn substitute(&self, bindings: &HashMap<String, SIDimension>) -> Option<SIDimension> {
+        let mut dim = self.known.clone();
+        for variable in &self.variables {
+            dim = dim.mul(&bindings.get(&variable.name)?.pow(variable.power));
+        }
+        Some(dim)
+    }
+
+    // Matches `dim`, binding the variable which is still unbound if there is one. Gives the
+    // declared dimension on a mismatch.
+    fn bind(
+        &self,
+        dim: &SIDimension,
+        bindings: &mut HashMap<String, SIDimension>,
+    ) -> Result<(), SIDimension> {
+        let unbound = self
+            .unbound(bindings)
+            .map(|v| (v.name.clone(), v.power))
+            .collect::<Vec<(String, Rational)>>();
+
+        match unbound.as_slice() {
+            [] => {
+                let declared = self.substitute(bindings).unwrap();
+                if declared == *dim {
+                    Ok(())
+                } else {
+                    Err(declared)
+                }
+            }
+            [(name, power)] => {
+                let mut rest = self.known.clone();
+                for variable in self.variables.iter().filter(|v| &v.name != name) {
+                    rest = rest.mul(&bindings[&variable.name].pow(variable.power));
+                }
+                let variable_dim = dim.mul(&rest.reciprocal()).pow(power.reciprocal());
+                bindings.insert(name.clone(), variable_dim);
+                Ok(())
+            }
+            // Cannot be determined, which is rejected when the function is defined
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Evaluator {
+    /// Matches the dimensions of the arguments against the declared ones in order, giving
+    /// the dimension variables the values of the first arguments using them. Unknown
+    /// dimensions match anything.
+    ///
+    /// Returns the dimension variables along with the mismatched arguments, by index and
+    /// declared dimension.
+    pub(super) fn match_params(
+        &self,
+        dims: &[Option<SIDimension>],
+    ) -> (HashMap<String, SIDimension>, Vec<(usize, SIDimension)>) {
+        let mut bindings = HashMap::new();
+        let mut mismatches = Vec::new();
+
+        for (i, (pattern, dim)) in self.signature.params.iter().zip(dims).enumerate() {
+            let (pattern, dim) = match pattern.as_ref().zip(dim.as_ref()) {
+                Some(declared) => declared,
+                None => continue,
+            };
+
+            if let Err(declared) = pattern.bind(dim, &mut bindings) {
+                mismatches.push((i, declared));
+            }
+        }
+
+        (bindings, mismatches)
+    }
+
+    /// The declared dimension of the result given the dimension variables, if there is one.
+    pub(super) fn declared_result(
+        &self,
+        bindings: &HashMap<String, SIDimension>,
+    ) -> Option<SIDimension> {
+        self.signature.result.as_ref()?.substitute(bindings)
+    }
+
+    /// Checks the arguments against the declared dimensions of the parameters, pointing at
+    /// the first argument which does not match.
+    pub(super) fn check_arguments(
+        &self,
+        args: &[Value],
+        arg_nodes: &[Node],
+    ) -> Result<(), NodeError> {
+        if self.signature.params.iter().all(Option::is_none) {
+            return Ok(());
+        }
+
+        let dims = args.iter().map(|arg| Some(arg.dim())).collect::<Vec<_>>();
+        match self.match_params(&dims).1.first() {
+            Some(&(i, ref declared)) => Err(NodeError {
+                content: NodeErrorContent::ParamDimensionError(
+                    self.params[i].clone(),
+                    declared.clone(),
+                    args[i].dim(),
+                ),
+                start: arg_nodes[i].start,
+                end: arg_nodes[i].end,
+            }),
+            None => Ok(()),
+        }
+    }
+}
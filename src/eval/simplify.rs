@@ -0,0 +1,355 @@
+use super::{
+    BinaryOp, Environment, Evaluator, Node, NodeContent, NodeError, UnaryOp, UnitExpr, UnitTerm,
+    builtin::get_builtin,
+    formula::{children, collect_variables},
+    node::{eval_unit_factors, split_log_unit},
+};
+use crate::{
+    rational::Rational,
+    value::{SIDimension, Value},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+// Most nodes in the body of a user function which is inlined at its calls
+const INLINE_LIMIT: usize = 16;
+
+// The user function each name called to while simplifying, or `None` for those which were
+// built-in functions at the time
+type Functions = HashMap<String, Option<Arc<Evaluator>>>;
+
+/// The result of `Environment::simplify`, along with the user functions it was built from.
+///
+/// Folded calls and inlined bodies are copies of the functions as they were, so the
+/// simplified node is only equivalent to the original one until any of them is redefined.
+/// `eval` checks for this and evaluates the original node instead from then on.
+#[derive(Debug, Clone)]
+pub struct Simplified {
+    original: Node,
+    node: Node,
+    functions: Functions,
+}
+
+impl Simplified {
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Whether each function the simplified node relies on is still the same in `env`.
+    pub fn is_current(&self, env: &Environment) -> bool {
+        self.functions.iter().all(|(func, evaluator)| {
+            match (evaluator, env.shared_evaluator(func)) {
+                (Some(evaluator), Some(current)) => Arc::ptr_eq(evaluator, current),
+                (None, None) => true,
+                _ => false,
+            }
+        })
+    }
+
+    pub fn eval(
+        &self,
+        env: &Environment,
+        params: &HashMap<String, Value>,
+    ) -> Result<Value, NodeError> {
+        if self.is_current(env) {
+            self.node.eval(env, params)
+        } else {
+            self.original.eval(env, params)
+        }
+    }
+}
+
+fn node_count(node: &Node) -> usize {
+    1 + children(node).into_iter().map(node_count).sum::<usize>()
+}
+
+fn is_rational(node: &Node, r: Rational) -> bool {
+    matches!(node.content, NodeContent::Value(Value::Rational(v)) if v == r)
+}
+
+fn is_value(node: &Node) -> bool {
+    matches!(node.content, NodeContent::Value(_))
+}
+
+// The body of an inlined function is a copy where each parameter is the argument, keeping
+// its own span, and everything else is at the span of the call
+fn substitute(body: &Node, params: &[String], args: &[Node], start: usize, end: usize) -> Node {
+    let sub = |node: &Node| Box::new(substitute(node, params, args, start, end));
+    let units_at_call = |units: &UnitExpr| UnitExpr {
+        factor: units.factor,
+        terms: units
+            .terms
+            .iter()
+            .map(|term| UnitTerm {
+                start,
+                end,
+                ..term.clone()
+            })
+            .collect(),
+    };
+
+    let content = match &body.content {
+        NodeContent::Variable(var) => match params.iter().position(|param| param == var) {
+            Some(i) => return args[i].clone(),
+            None => NodeContent::Variable(var.clone()),
+        },
+        NodeContent::Value(val) => NodeContent::Value(val.clone()),
+        NodeContent::Binary(lhs, op, rhs) => NodeContent::Binary(sub(lhs), *op, sub(rhs)),
+        NodeContent::Unary(op, operand) => {
+            let op = match op {
+                UnaryOp::Units(units) => UnaryOp::Units(units_at_call(units)),
+                UnaryOp::Convert(units) => UnaryOp::Convert(units_at_call(units)),
+                UnaryOp::ConvertMixed(unit_groups) => {
+                    UnaryOp::ConvertMixed(unit_groups.iter().map(units_at_call).collect())
+                }
+                op => op.clone(),
+            };
+            NodeContent::Unary(op, sub(operand))
+        }
+        NodeContent::Function(func, param_nodes) => NodeContent::Function(
+            func.clone(),
+            param_nodes
+                .iter()
+                .map(|node| substitute(node, params, args, start, end))
+                .collect(),
+        ),
+        NodeContent::Uncertain(value, sigma, source) => {
+            NodeContent::Uncertain(sub(value), sub(sigma), source.clone())
+        }
+        NodeContent::Conditional(condition, if_true, if_false) => {
+            NodeContent::Conditional(sub(condition), sub(if_true), sub(if_false))
+        }
+    };
+
+    Node {
+        content,
+        start,
+        end,
+    }
+}
+
+impl Environment {
+    /// An equivalent of the node which is quicker to evaluate many times, such as over a sweep of
+    /// parameters.
+    ///
+    /// Parts without variables are evaluated once, unit suffixes on other values become a
+    /// multiplication by the precomputed conversion, and `x*1`, `x/1`, `x^1` and `x+0` are
+    /// just `x`. Calls of small user functions with values or variables as arguments are
+    /// replaced by the body of the function. Rationals stay exact and errors keep their
+    /// spans, except that an error in the body of an inlined function is reported at the
+    /// call rather than nested within it. Parts which fail to evaluate are left as they are
+    /// so that the error comes up when the result is evaluated.
+    ///
+    /// Constants are not folded, so the result stays correct when they are redefined, while
+    /// user functions are, see `Simplified`.
+    pub fn simplify(&self, node: &Node) -> Simplified {
+        let mut functions = Functions::new();
+        Simplified {
+            original: node.clone(),
+            node: self.simplify_node(node, &mut functions),
+            functions,
+        }
+    }
+
+    fn simplify_node(&self, node: &Node, functions: &mut Functions) -> Node {
+        let (start, end) = (node.start, node.end);
+        let mut simplified = |node: &Node| Box::new(self.simplify_node(node, functions));
+
+        let content = match &node.content {
+            NodeContent::Binary(lhs, op, rhs) => {
+                NodeContent::Binary(simplified(lhs), *op, simplified(rhs))
+            }
+            NodeContent::Unary(op, operand) => NodeContent::Unary(op.clone(), simplified(operand)),
+            NodeContent::Function(func, param_nodes) => NodeContent::Function(
+                func.clone(),
+                param_nodes.iter().map(|node| *simplified(node)).collect(),
+            ),
+            NodeContent::Uncertain(value, sigma, source) => {
+                NodeContent::Uncertain(simplified(value), simplified(sigma), source.clone())
+            }
+            NodeContent::Conditional(condition, if_true, if_false) => {
+                let condition = simplified(condition);
+                // A constant scalar condition chooses the branch once and for all
+                if let NodeContent::Value(v) = &condition.content {
+                    match v.scalar_truth() {
+                        Ok(Some(true)) => return *simplified(if_true),
+                        Ok(Some(false)) => return *simplified(if_false),
+                        _ => (),
+                    }
+                }
+                NodeContent::Conditional(condition, simplified(if_true), simplified(if_false))
+            }
+            NodeContent::Value(_) | NodeContent::Variable(_) => return node.clone(),
+        };
+        let node = Node {
+            content,
+            start,
+            end,
+        };
+
+        if self.is_constant(&node, functions) {
+            return match node.eval(self, &HashMap::new()) {
+                Ok(value) => Node {
+                    content: NodeContent::Value(value),
+                    start,
+                    end,
+                },
+                Err(_) => node,
+            };
+        }
+        self.rewrite(node, functions)
+    }
+
+    // Records what each function called by the node is now, including those called through
+    // user functions, giving whether any of them or the node uses variables
+    fn record_calls(&self, node: &Node, functions: &mut Functions) -> bool {
+        let mut variables = Vec::new();
+        let mut visited = HashSet::new();
+        collect_variables(self, node, &[], &mut visited, &mut variables);
+        for func in visited {
+            functions
+                .entry(func.into())
+                .or_insert_with(|| self.shared_evaluator(func).cloned());
+        }
+        !variables.is_empty()
+    }
+
+    // Whether the node only depends on values, which for a call includes the constants used
+    // by user functions
+    fn is_constant(&self, node: &Node, functions: &mut Functions) -> bool {
+        children(node).into_iter().all(is_value) && !self.record_calls(node, functions)
+    }
+
+    // The dimension of a call depends on the body of the function
+    fn is_dimless(&self, node: &Node, functions: &mut Functions) -> bool {
+        self.record_calls(node, functions);
+        matches!(
+            self.check_dimensions(node, &HashMap::new()),
+            Ok(Some(dim)) if dim == SIDimension::DIMLESS
+        )
+    }
+
+    fn rewrite(&self, node: Node, functions: &mut Functions) -> Node {
+        let Node {
+            content,
+            start,
+            end,
+        } = node;
+
+        let content = match content {
+            NodeContent::Binary(lhs, op, rhs) => match op {
+                BinaryOp::Mul if is_rational(&rhs, Rational::ONE) => return *lhs,
+                BinaryOp::Mul if is_rational(&lhs, Rational::ONE) => return *rhs,
+                BinaryOp::Div | BinaryOp::Pow if is_rational(&rhs, Rational::ONE) => return *lhs,
+                // Adding zero is a dimension error unless the other side is dimensionless
+                BinaryOp::Add | BinaryOp::Sub
+                    if is_rational(&rhs, Rational::ZERO) && self.is_dimless(&lhs, functions) =>
+                {
+                    return *lhs;
+                }
+                BinaryOp::Add
+                    if is_rational(&lhs, Rational::ZERO) && self.is_dimless(&rhs, functions) =>
+                {
+                    return *rhs;
+                }
+                op => NodeContent::Binary(lhs, op, rhs),
+            },
+            NodeContent::Unary(UnaryOp::Units(units), operand) => {
+                match split_log_unit(&units, self) {
+                    Ok((None, terms)) => match eval_unit_factors(units.factor, terms, self) {
+                        Ok(conversion) => {
+                            let conversion = Node {
+                                content: NodeContent::Value(conversion.into()),
+                                start,
+                                end,
+                            };
+                            NodeContent::Binary(operand, BinaryOp::Mul, Box::new(conversion))
+                        }
+                        Err(_) => NodeContent::Unary(UnaryOp::Units(units), operand),
+                    },
+                    _ => NodeContent::Unary(UnaryOp::Units(units), operand),
+                }
+            }
+            NodeContent::Function(func, param_nodes) => {
+                match self.inline(&func, &param_nodes, start, end, functions) {
+                    Some(body) => return self.simplify_node(&body, functions),
+                    None => NodeContent::Function(func, param_nodes),
+                }
+            }
+            content => content,
+        };
+
+        Node {
+            content,
+            start,
+            end,
+        }
+    }
+
+    // Only functions without declared dimensions whose body is small, uses each parameter
+    // and nothing else, and calls no user functions are inlined. Any other argument than a
+    // value or variable would be evaluated more or fewer times than before.
+    fn inline(
+        &self,
+        func: &str,
+        args: &[Node],
+        start: usize,
+        end: usize,
+        functions: &mut Functions,
+    ) -> Option<Node> {
+        let evaluator = self.shared_evaluator(func)?;
+        if evaluator.params.len() != args.len()
+            || evaluator.param_dims.iter().any(Option::is_some)
+            || node_count(&evaluator.parent) > INLINE_LIMIT
+            || !args.iter().all(|arg| {
+                matches!(
+                    arg.content,
+                    NodeContent::Value(_) | NodeContent::Variable(_)
+                )
+            })
+        {
+            return None;
+        }
+
+        let mut used = HashSet::new();
+        if !self.inlinable(&evaluator.parent, &evaluator.params, &mut used)
+            || used.len() != evaluator.params.len()
+        {
+            return None;
+        }
+
+        functions.insert(func.into(), Some(evaluator.clone()));
+        Some(substitute(
+            &evaluator.parent,
+            &evaluator.params,
+            args,
+            start,
+            end,
+        ))
+    }
+
+    fn inlinable<'a>(
+        &self,
+        node: &'a Node,
+        params: &[String],
+        used: &mut HashSet<&'a str>,
+    ) -> bool {
+        let allowed = match &node.content {
+            NodeContent::Variable(var) => {
+                used.insert(var.as_str());
+                params.contains(var)
+            }
+            NodeContent::Function(func, _) => {
+                self.get_evaluator(func).is_none() && get_builtin(func).is_some()
+            }
+            _ => true,
+        };
+
+        allowed
+            && children(node)
+                .into_iter()
+                .all(|child| self.inlinable(child, params, used))
+    }
+}
@@ -0,0 +1,58 @@
+use super::{SourceRegistry, SourceSlot, UncertaintySource};
+use crate::value::SourceId;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+impl SourceRegistry {
+    /// Numbers a new source, which may have the same name as an earlier one. Gives `None`
+    /// once every number has been given out.
+    pub fn register(&self, name: &str) -> Option<SourceId> {
+        let mut names = self.names.write().unwrap();
+        let id = SourceId(u32::try_from(names.len()).ok()?);
+        names.push(name.into());
+        Some(id)
+    }
+
+    pub fn name(&self, id: SourceId) -> Option<String> {
+        self.names.read().unwrap().get(id.0 as usize).cloned()
+    }
+}
+
+impl UncertaintySource {
+    /// A source not yet registered, `name` being how it was written.
+    pub fn new(name: String) -> Self {
+        Self(Arc::new(SourceSlot {
+            name,
+            first: OnceLock::new(),
+            others: Mutex::new(Vec::new()),
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// The number of the source in `registry`, registering it there on first use.
+    pub fn id(&self, registry: &Arc<SourceRegistry>) -> Option<SourceId> {
+        let numbered_by = |(owner, id): &(Weak<SourceRegistry>, SourceId)| {
+            std::ptr::eq(owner.as_ptr(), Arc::as_ptr(registry)).then_some(*id)
+        };
+        if let Some(id) = self.0.first.get().and_then(numbered_by) {
+            return Some(id);
+        }
+
+        // Registering under the lock, so that a source is never numbered twice
+        let mut others = self.0.others.lock().unwrap();
+        if let Some(id) = self.0.first.get().and_then(numbered_by) {
+            return Some(id);
+        }
+        if let Some(id) = others.iter().find_map(numbered_by) {
+            return Some(id);
+        }
+
+        let id = registry.register(&self.0.name)?;
+        if let Err(entry) = self.0.first.set((Arc::downgrade(registry), id)) {
+            others.push(entry);
+        }
+        Some(id)
+    }
+}
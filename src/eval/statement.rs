@@ -0,0 +1,91 @@
+use super::{
+    ConversionValue, Environment, Node, NodeError, NodeErrorContent, ShadowWarning, Statement,
+};
+use crate::{f64plus::FloatPlus, value::Value};
+use std::collections::HashMap;
+
+impl Statement {
+    /// Runs the statement, giving the value of an expression or defined constant, or the size
+    /// of a declared unit. Defining a function gives no value. A definition which hides a
+    /// built-in or default name also gives a warning.
+    pub fn execute(
+        &self,
+        env: &mut Environment,
+        params: &HashMap<String, Value>,
+    ) -> Result<(Option<Value>, Option<ShadowWarning>), NodeError> {
+        match self {
+            Self::Expression(node) => Ok((Some(node.eval(env, params)?), None)),
+            Self::ConstDefinition { name, value, .. } => {
+                let warning = env.define_formula(name, value.clone())?;
+                Ok((Some(env.get_const(name).unwrap().clone()), warning))
+            }
+            // The spans of a function definition are within the same line as its body
+            Self::FunctionDefinition {
+                name, evaluator, ..
+            } => match env.define_function(name, evaluator.clone()) {
+                Ok(warning) => Ok((None, warning)),
+                Err(e) => Err(NodeError {
+                    content: e.content,
+                    start: e.start,
+                    end: e.end,
+                }),
+            },
+            Self::UnitDeclaration {
+                name,
+                value,
+                start,
+                end,
+            } => {
+                let size = declare_unit(name, value, env, params, *start, *end)?;
+                Ok((Some(size), None))
+            }
+        }
+    }
+}
+
+fn declare_unit(
+    name: &str,
+    value: &Node,
+    env: &mut Environment,
+    params: &HashMap<String, Value>,
+    start: usize,
+    end: usize,
+) -> Result<Value, NodeError> {
+    // Exact names would win over prefixed readings, but declaring e.g. `min` when it already
+    // reads as milli-`in` would silently change what existing input means
+    if env.resolve_unit(name).is_some() || env.get_log_unit(name).is_some() {
+        return Err(NodeError {
+            content: NodeErrorContent::UnitExistsError(name.into()),
+            start,
+            end,
+        });
+    }
+
+    let result = value.eval(env, params)?;
+
+    let conversion = match result.try_promote_quantity() {
+        Some(q) => match q.value {
+            FloatPlus::Scalar(factor) if q.derivatives.is_empty() => {
+                Some(ConversionValue { factor, dim: q.dim })
+            }
+            _ => None,
+        },
+        None => None,
+    };
+
+    // A unit of size zero, infinity or NaN could never be converted back into, and fractional
+    // powers of a negative unit are not real
+    let content = match conversion {
+        Some(conversion) if conversion.factor.is_normal() && conversion.factor > 0. => {
+            env.units.insert(name.into(), conversion);
+            return Ok(result);
+        }
+        Some(conversion) if conversion.factor.is_normal() => NodeErrorContent::NegativeUnitValue,
+        _ => NodeErrorContent::NotPlainUnitValue,
+    };
+    Err(NodeError {
+        content,
+        start: value.start,
+        end: value.end,
+    })
+}
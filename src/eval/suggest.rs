@@ -0,0 +1,217 @@
+use super::{
+    Environment,
+    builtin::{builtin_const_names, builtin_names},
+};
+use crate::{utils::edit_distance, value::Value};
+use std::collections::HashMap;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+// Spelled out names commonly typed instead of unit symbols, matched case-insensitively
+// after removing a plural ending
+const UNIT_ALIASES: [(&str, &str); 36] = [
+    ("meter", "m"),
+    ("metre", "m"),
+    ("second", "s"),
+    ("sec", "s"),
+    ("gram", "g"),
+    ("kilo", "kg"),
+    ("minute", "min"),
+    ("hour", "h"),
+    ("hr", "h"),
+    ("day", "d"),
+    ("year", "yr"),
+    ("liter", "L"),
+    ("litre", "L"),
+    ("newton", "N"),
+    ("joule", "J"),
+    ("watt", "W"),
+    ("volt", "V"),
+    ("ampere", "A"),
+    ("amp", "A"),
+    ("kelvin", "K"),
+    ("mole", "mol"),
+    ("hertz", "Hz"),
+    ("pascal", "Pa"),
+    ("ohm", "Ω"),
+    ("radian", "rad"),
+    ("degree", "deg"),
+    ("steradian", "sr"),
+    ("candela", "cd"),
+    ("bit", "bit"),
+    ("byte", "B"),
+    ("foot", "ft"),
+    ("feet", "ft"),
+    ("inch", "in"),
+    ("pound", "lb"),
+    ("mile", "mi"),
+    ("ounce", "oz"),
+];
+
+const PREFIX_ALIASES: [(&str, &str); 12] = [
+    ("tera", "T"),
+    ("giga", "G"),
+    ("mega", "M"),
+    ("kilo", "k"),
+    ("hecto", "h"),
+    ("deca", "da"),
+    ("deci", "d"),
+    ("centi", "c"),
+    ("milli", "m"),
+    ("micro", "µ"),
+    ("nano", "n"),
+    ("pico", "p"),
+];
+
+// Names shorter than this are only matched ignoring case, as nearly every other short name
+// is a single edit away from them
+const MIN_FUZZY_LENGTH: usize = 3;
+
+fn lowercase_start(name: &str) -> Option<char> {
+    name.chars().next().and_then(|c| c.to_lowercase().next())
+}
+
+// The edit distance from `name` to the candidate when it is close enough to suggest, where
+// a case-insensitive match counts as zero. Other matches must start with the same letter.
+fn similarity(name: &str, candidate: &str) -> Option<usize> {
+    if candidate.to_lowercase() == name.to_lowercase() {
+        return Some(0);
+    }
+
+    let length = name.chars().count();
+    if length < MIN_FUZZY_LENGTH || lowercase_start(name) != lowercase_start(candidate) {
+        return None;
+    }
+    let distance = edit_distance(name, candidate);
+    (distance <= (length / 3).max(1)).then_some(distance)
+}
+
+// Candidates are ranked by distance, ties keep the order the candidates are given in
+fn rank(scored: impl Iterator<Item = (usize, String)>) -> Vec<String> {
+    let mut ranked = scored.collect::<Vec<(usize, String)>>();
+    ranked.sort_by_key(|&(distance, _)| distance);
+
+    let mut suggestions = Vec::new();
+    for (_, candidate) in ranked {
+        push_unique(&mut suggestions, candidate);
+    }
+    suggestions
+}
+
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    rank(candidates.filter_map(|candidate| {
+        similarity(name, candidate).map(|distance| (distance, candidate.into()))
+    }))
+}
+
+fn push_unique(suggestions: &mut Vec<String>, suggestion: String) {
+    if suggestions.len() < MAX_SUGGESTIONS && !suggestions.contains(&suggestion) {
+        suggestions.push(suggestion);
+    }
+}
+
+fn find_alias<'a>(name: &str, aliases: &[(&str, &'a str)]) -> Option<&'a str> {
+    aliases
+        .iter()
+        .find(|&&(alias, _)| alias == name)
+        .map(|&(_, symbol)| symbol)
+}
+
+// `meters` is read as `m` and `kilometres` as `km`
+fn unit_alias(name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    let singulars = [
+        Some(name.as_str()),
+        name.strip_suffix('s'),
+        name.strip_suffix("es"),
+    ];
+
+    for singular in singulars.into_iter().flatten() {
+        if let Some(symbol) = find_alias(singular, &UNIT_ALIASES) {
+            return Some(symbol.into());
+        }
+
+        for &(long_prefix, prefix) in &PREFIX_ALIASES {
+            let symbol = singular
+                .strip_prefix(long_prefix)
+                .and_then(|unit| find_alias(unit, &UNIT_ALIASES));
+            if let Some(symbol) = symbol {
+                return Some(format!("{prefix}{symbol}"));
+            }
+        }
+    }
+
+    None
+}
+
+impl Environment {
+    /// Units similar to the unknown unit `name`, including prefixed units and the symbols of
+    /// spelled out names like `meters`.
+    pub fn suggest_units(&self, name: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        if let Some(symbol) = unit_alias(name).filter(|symbol| self.resolve_unit(symbol).is_some())
+        {
+            suggestions.push(symbol);
+        }
+
+        let units = self.visible_units();
+        let mut unit_names = units
+            .keys()
+            .copied()
+            .chain(self.visible_log_units().into_keys())
+            .collect::<Vec<_>>();
+        unit_names.sort();
+        let mut prefixed_names = self
+            .visible_prefixes()
+            .into_keys()
+            .flat_map(|prefix| units.keys().map(move |&unit| (prefix, unit)))
+            .collect::<Vec<(&str, &str)>>();
+        prefixed_names.sort();
+
+        // A prefixed unit is only suggested for a name with the same prefix, by how close
+        // the rest is to the unit, so `mtr` is not taken for `msr`. Unprefixed units come
+        // first so they win ties against prefixed ones.
+        let prefixed = prefixed_names.into_iter().filter_map(|(prefix, unit)| {
+            let candidate = format!("{prefix}{unit}");
+            let distance = if candidate.to_lowercase() == name.to_lowercase() {
+                0
+            } else {
+                similarity(name.strip_prefix(prefix)?, unit)?
+            };
+            Some((distance, candidate))
+        });
+        let unprefixed = unit_names
+            .into_iter()
+            .filter_map(|unit| similarity(name, unit).map(|distance| (distance, unit.to_string())));
+        for suggestion in rank(unprefixed.chain(prefixed)) {
+            push_unique(&mut suggestions, suggestion);
+        }
+
+        suggestions
+    }
+
+    /// Parameters and constants similar to the unknown variable `name`.
+    pub fn suggest_vars(&self, name: &str, params: &HashMap<String, Value>) -> Vec<String> {
+        let mut var_names = params
+            .keys()
+            .map(String::as_str)
+            .chain(self.visible_consts().into_keys())
+            .chain(self.visible_formulas().into_keys())
+            .collect::<Vec<_>>();
+        var_names.sort();
+        let candidates = var_names
+            .into_iter()
+            .chain(builtin_const_names().map(|name| -> &str { name }));
+        suggest(name, candidates)
+    }
+
+    /// User functions and built-in functions similar to the unknown function `name`.
+    pub fn suggest_funcs(&self, name: &str) -> Vec<String> {
+        let mut evaluator_names = self.visible_evaluators().into_keys().collect::<Vec<_>>();
+        evaluator_names.sort();
+        let candidates = evaluator_names
+            .into_iter()
+            .chain(builtin_names().map(|name| -> &str { name }));
+        suggest(name, candidates)
+    }
+}
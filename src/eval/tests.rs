@@ -0,0 +1,915 @@
+use super::{
+    AngleMode, CallLimit, ConversionValue, Environment, EvalLimits, NodeContent, NodeError,
+    NodeErrorContent, ShadowWarning, Statement, units,
+};
+use crate::{
+    f64plus::FloatPlus,
+    parse::parse_line,
+    rational::Rational,
+    value::{Complex, DimensionTable, Quantity, SIDimension, SourceId, Value, ValueError},
+};
+use std::{collections::HashMap, sync::Arc};
+
+fn environment(angle_mode: AngleMode) -> Environment {
+    let mut env = Environment {
+        consts: HashMap::new(),
+        formulas: HashMap::new(),
+        evaluators: HashMap::new(),
+        units: HashMap::from_iter(units::angle_units()),
+        prefixes: HashMap::from_iter(units::si_prefixes()),
+        log_units: HashMap::from_iter(units::log_units()),
+        dimensions: DimensionTable::default(),
+        angle_mode,
+        limits: EvalLimits::default(),
+        parallelism: Default::default(),
+        sources: Default::default(),
+        parent: None,
+    };
+    env.load_units(
+        "test",
+        "dimension s\ndimension m\ndimension kg\nmin = 60 s\nh = 60 min\nW = kg m^2 / s^3",
+    )
+    .unwrap();
+    env
+}
+
+fn run(env: &mut Environment, line: &str) -> Result<Option<Value>, NodeErrorContent> {
+    let statement = parse_line(line).unwrap_or_else(|e| panic!("`{line}` fails to parse: {e:?}"));
+    match statement.execute(env, &HashMap::new()) {
+        Ok((value, _)) => Ok(value),
+        Err(e) => Err(e.content),
+    }
+}
+
+fn value(env: &mut Environment, line: &str) -> Value {
+    match run(env, line) {
+        Ok(Some(value)) => value,
+        Ok(None) => panic!("`{line}` gives no value"),
+        Err(e) => panic!("`{line}` fails: {}", e.describe(&env.dimensions)),
+    }
+}
+
+fn value_error(env: &mut Environment, line: &str) -> ValueError {
+    match run(env, line) {
+        Err(NodeErrorContent::ValueError(e)) => e,
+        Err(NodeErrorContent::DimensionErrors(errors, _)) => match &errors[0].content {
+            NodeErrorContent::ValueError(e) => e.clone(),
+            e => panic!("`{line}` fails with {e:?}"),
+        },
+        result => panic!("`{line}` gives {result:?} rather than a value error"),
+    }
+}
+
+#[test]
+fn strict_angles_in_functions() {
+    let mut env = environment(AngleMode::Strict);
+    let angle = SIDimension::base(SIDimension::ANGLE);
+
+    assert_eq!(value(&mut env, "sin(90 [deg])").dim(), SIDimension::DIMLESS);
+    assert_eq!(value(&mut env, "cos(1)").dim(), SIDimension::DIMLESS);
+    assert_eq!(value(&mut env, "asin(1)").dim(), angle);
+
+    for line in ["sin(2 [rad^2])", "tan(1 [sr])", "cos(1 [m])"] {
+        assert!(matches!(
+            value_error(&mut env, line),
+            ValueError::NotAngleOperand(_)
+        ));
+    }
+    for line in [
+        "exp(1 [rad])",
+        "ln(2 [rad])",
+        "log10(2 [sr])",
+        "log(2 [rad], 10)",
+        "asin(0.5 [rad])",
+        "atan(1 [rad])",
+        "sinh(1 [rad])",
+        "gamma(1 [rad])",
+    ] {
+        assert!(matches!(
+            value_error(&mut env, line),
+            ValueError::NotDimensionlessOperand(_)
+        ));
+    }
+}
+
+#[test]
+fn strict_angles_in_function_definitions() {
+    let mut env = environment(AngleMode::Strict);
+
+    assert!(run(&mut env, "f(x [rad]) = sin(x)").is_ok());
+    assert!(matches!(
+        run(&mut env, "g(x [rad]) = exp(x)"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+}
+
+#[test]
+fn prefix_minus() {
+    let mut env = environment(AngleMode::Dimensionless);
+
+    assert!(matches!(value(&mut env, "x = -2"), Value::Rational(r) if r == (-2).into()));
+    assert!(matches!(value(&mut env, "-x²"), Value::Rational(r) if r == (-4).into()));
+    match value(&mut env, "-30 [dBm] -> [W]") {
+        Value::Quantity(Quantity {
+            value: FloatPlus::Scalar(x),
+            ..
+        }) => assert!((x - 1e-6).abs() < 1e-15),
+        v => panic!("-30 dBm is {v:?}"),
+    }
+}
+
+#[test]
+fn mixed_units_keep_the_quantity() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let time = SIDimension::base(SIDimension::TIME);
+
+    let parts = |value: Value| match value {
+        Value::Mixed(mixed) => {
+            assert_eq!(mixed.quantity.dim, time);
+            mixed
+                .parts
+                .into_iter()
+                .map(|(amount, units)| match amount.value {
+                    FloatPlus::Scalar(x) => (x, units),
+                    FloatPlus::Vector(_) => panic!("{units} has a vector amount"),
+                })
+                .collect::<Vec<_>>()
+        }
+        v => panic!("{v:?} is not split into units"),
+    };
+    let split = parts(value(&mut env, "4805.5 [s] -> [h] [min] [s]"));
+    assert_eq!(
+        split,
+        [
+            (1., "[h]".into()),
+            (20., "[min]".into()),
+            (5.5, "[s]".into())
+        ]
+    );
+    assert_eq!(parts(value(&mut env, "4805.5 [s] -> [h:min:s]")), split);
+
+    assert!(units::decompose(7265., &[]).is_empty());
+
+    // Each unit must be smaller than the one before
+    for line in [
+        "7265 [s] -> [s] [h]",
+        "7265 [s] -> [h:min:min]",
+        "1 [h] -> [min:h:s]",
+    ] {
+        assert!(
+            matches!(
+                run(&mut env, line),
+                Err(NodeErrorContent::UnorderedMixedUnits)
+            ),
+            "{line}"
+        );
+    }
+
+    let sum = value(&mut env, "(4805 [s] -> [h] [min] [s]) + 1 [min]");
+    assert_eq!(sum.dim(), time);
+    assert!(run(&mut env, "f(t [s]) = t -> [h] [min]").is_ok());
+    assert!(matches!(
+        run(&mut env, "g(t [m]) = t -> [h] [min]"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+}
+
+#[test]
+fn uncertainty_sources() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let sources = |value: Value| match value {
+        Value::Quantity(q) => q.derivatives.sources().collect::<Vec<SourceId>>(),
+        v => panic!("{v:?} has no derivatives"),
+    };
+
+    // Each ± parsed is a source of its own, which a definition keeps
+    value(&mut env, "a = 2 ± 0.1");
+    let a = sources(value(&mut env, "a"));
+    assert_eq!(a.len(), 1);
+    assert_eq!(sources(value(&mut env, "a * a")), a);
+    assert_eq!(sources(value(&mut env, "2 ± 0.1")).len(), 1);
+    assert_ne!(sources(value(&mut env, "2 ± 0.1")), a);
+    assert_eq!(sources(value(&mut env, "a + 2 ± 0.1")).len(), 2);
+
+    assert_eq!(env.source_name(a[0]).unwrap(), "2 ± 0.1");
+
+    // Scopes share the numbers of the environment they are in, other environments have
+    // their own
+    let mut session = Environment::child(Arc::new(env));
+    assert_eq!(sources(value(&mut session, "a")), a);
+    let b = sources(value(&mut session, "3 ± 0.1"));
+    assert!(!a.contains(&b[0]));
+    assert_eq!(session.source_name(b[0]).unwrap(), "3 ± 0.1");
+
+    let mut other = environment(AngleMode::Dimensionless);
+    assert_eq!(sources(value(&mut other, "3 ± 0.1")), [SourceId(0)]);
+    assert_eq!(other.source_name(SourceId(0)).unwrap(), "3 ± 0.1");
+    assert!(other.source_name(SourceId(1)).is_none());
+
+    // A node evaluated in two environments keeps its number in each
+    let node = match parse_line("4 ± 0.1").unwrap() {
+        Statement::Expression(node) => node,
+        s => panic!("{s:?} is not an expression"),
+    };
+    let numbered = |env: &Environment| sources(node.eval(env, &HashMap::new()).unwrap());
+    let (in_session, in_other) = (numbered(&session), numbered(&other));
+    assert_eq!(in_other, [SourceId(1)]);
+    assert_eq!(numbered(&session), in_session);
+    assert_eq!(numbered(&other), in_other);
+}
+
+#[test]
+fn shadowing_definitions_warn() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let warning = |env: &mut Environment, line: &str| {
+        let statement = parse_line(line).unwrap();
+        statement.execute(env, &HashMap::new()).unwrap().1
+    };
+
+    assert!(matches!(
+        warning(&mut env, "pi = 3"),
+        Some(ShadowWarning::Builtin(name)) if name == "pi"
+    ));
+    assert!(matches!(
+        warning(&mut env, "sin(x) = x"),
+        Some(ShadowWarning::Builtin(name)) if name == "sin"
+    ));
+    assert!(warning(&mut env, "y = 3").is_none());
+
+    let mut session = Environment::child(Arc::new(env));
+    assert!(matches!(
+        warning(&mut session, "y = 4"),
+        Some(ShadowWarning::Default(name)) if name == "y"
+    ));
+}
+
+#[test]
+fn redefined_functions_update_formulas() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let rational = |value: Value| match value {
+        Value::Rational(r) => r,
+        v => panic!("{v:?} is not rational"),
+    };
+
+    assert!(run(&mut env, "f(x) = 2 x").is_ok());
+    assert!(run(&mut env, "g(x) = f(x) + 1").is_ok());
+    value(&mut env, "a = g(3)");
+    value(&mut env, "b = a + 1");
+    value(&mut env, "c = sin(0)");
+    assert!(run(&mut env, "f(x) = 3 x").is_ok());
+    assert!(run(&mut env, "sin(x) = 1").is_ok());
+
+    assert!(rational(value(&mut env, "a")) == 10.into());
+    assert!(rational(value(&mut env, "b")) == 11.into());
+    assert!(rational(value(&mut env, "c")) == 1.into());
+
+    // A new body may use constants the old one did not
+    value(&mut env, "k = 5");
+    assert!(run(&mut env, "f(x) = k x").is_ok());
+    assert!(rational(value(&mut env, "a")) == 16.into());
+    value(&mut env, "k = 7");
+    assert!(rational(value(&mut env, "a")) == 22.into());
+    assert!(rational(value(&mut env, "b")) == 23.into());
+
+    // Or the formula calling it, which leaves it cyclic until the body changes back
+    assert!(run(&mut env, "f(x) = a").is_ok());
+    assert!(matches!(
+        env.get_formula("a").unwrap().value,
+        Err(NodeError {
+            content: NodeErrorContent::CyclicConstError(_),
+            ..
+        })
+    ));
+    assert!(matches!(
+        run(&mut env, "b"),
+        Err(NodeErrorContent::FormulaError(name, _)) if name == "b"
+    ));
+    assert!(run(&mut env, "f(x) = x").is_ok());
+    assert!(rational(value(&mut env, "a")) == 4.into());
+    assert!(rational(value(&mut env, "b")) == 5.into());
+}
+
+#[test]
+fn conditionals_with_dimensions_of_their_own() {
+    let mut env = environment(AngleMode::Dimensionless);
+
+    assert!(run(&mut env, "g(x) = if(x > 0, 1 [m], 0)").is_ok());
+    assert_eq!(
+        value(&mut env, "g(2)").dim(),
+        SIDimension::base(SIDimension::LENGTH)
+    );
+    assert_eq!(value(&mut env, "g(0 - 2)").dim(), SIDimension::DIMLESS);
+
+    // The condition itself must still be dimensionless
+    assert!(matches!(
+        run(&mut env, "h(x [m]) = if(x, 1, 0)"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+}
+
+#[test]
+fn declared_dimensions() {
+    let mut env = environment(AngleMode::Dimensionless);
+
+    assert!(run(&mut env, "ke(m [kg], v [m/s]) [kg m^2/s^2] = m v² / 2").is_ok());
+    assert!(matches!(
+        run(&mut env, "ke(2 [kg], 3 [s])"),
+        Err(NodeErrorContent::ParamDimensionError(param, ..)) if param == "v"
+    ));
+    // The units are looked up once, when the function is defined
+    let signature = &env.get_evaluator("ke").unwrap().signature;
+    assert!(signature.params.iter().all(Option::is_some) && signature.result.is_some());
+    assert!(run(&mut env, "sq(x [D]) [D^2] = x²").is_ok());
+    assert_eq!(
+        value(&mut env, "sq(3 [m])").dim(),
+        SIDimension::base(SIDimension::LENGTH).pow(2.into())
+    );
+    assert!(matches!(
+        run(&mut env, "cube(x [D]) [D^2] = x³"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+    assert!(run(&mut env, "same(x [Length], y [Length]) = x + y").is_ok());
+    assert!(matches!(
+        run(&mut env, "same(1 [m], 2 [s])"),
+        Err(NodeErrorContent::ParamDimensionError(param, ..)) if param == "y"
+    ));
+
+    // A leading ' makes even a unit name a variable
+    assert!(run(&mut env, "sq2(x ['m]) ['m^2] = x x").is_ok());
+    assert_eq!(
+        value(&mut env, "sq2(3 [s])").dim(),
+        value(&mut env, "1 [s^2]").dim()
+    );
+
+    // A misspelt unit is not taken for a dimension variable
+    match run(&mut env, "f(m [kgg]) = m") {
+        Err(NodeErrorContent::DimensionErrors(errors, _)) => assert!(matches!(
+            &errors[0].content,
+            NodeErrorContent::UnitNameError(name, _) if name == "kgg"
+        )),
+        result => panic!("`f(m [kgg]) = m` gives {result:?}"),
+    }
+    assert!(matches!(
+        run(&mut env, "g(x) [D] = x"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+}
+
+#[test]
+fn compiled_user_functions() {
+    let mut env = environment(AngleMode::Dimensionless);
+    assert!(run(&mut env, "sq(x) = x x").is_ok());
+    assert!(run(&mut env, "fact(n) = if(n < 2, 1, n fact(n - 1))").is_ok());
+    assert!(run(&mut env, "f(x) = sq(x) + fact(4) + y").is_ok());
+    value(&mut env, "y = 1");
+
+    let params = ["x".to_string()];
+    let node = match parse_line("f(x) + sq(x)").unwrap() {
+        Statement::Expression(node) => node,
+        _ => unreachable!(),
+    };
+    let compiled = node.compile(&params);
+    let rational = |value: Result<Value, _>| match value {
+        Ok(Value::Rational(r)) => r,
+        v => panic!("{v:?} is not rational"),
+    };
+    let both = |env: &Environment, x: i32| {
+        let args = [Value::Rational(x.into())];
+        let named = HashMap::from([("x".to_string(), args[0].clone())]);
+        let compiled = rational(compiled.eval(env, &args));
+        assert!(compiled == rational(node.eval(env, &named)));
+        compiled
+    };
+
+    assert!(both(&env, 3) == 43.into());
+    assert!(run(&mut env, "sq(x) = 2 x").is_ok());
+    assert!(both(&env, 3) == 37.into());
+
+    assert!(matches!(
+        compiled.eval(&env, &[]),
+        Err(NodeError {
+            content: NodeErrorContent::ParamCountError(1, 0),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn simplified_nodes() {
+    let mut env = environment(AngleMode::Dimensionless);
+    assert!(run(&mut env, "g(x) = 3 x").is_ok());
+    assert!(run(&mut env, "k(x) = x + 1").is_ok());
+    let args = HashMap::from([
+        ("x".to_string(), Value::Rational(2.into())),
+        ("z".to_string(), Value::Rational(2.into())),
+    ]);
+    let simplify = |env: &Environment, line: &str| {
+        let node = match parse_line(line).unwrap() {
+            Statement::Expression(node) => node,
+            _ => unreachable!(),
+        };
+        let simplified = env.simplify(&node);
+        (node, simplified)
+    };
+    let repr =
+        |env: &Environment, line: &str| simplify(env, line).1.node().repr_with(&env.dimensions);
+
+    // Folding keeps rationals exact
+    assert!(matches!(
+        simplify(&env, "1/3 + 1/6").1.node().content,
+        NodeContent::Value(Value::Rational(r)) if r == Rational::new(1, 2)
+    ));
+    assert_eq!(repr(&env, "2 + sin(0) x"), "(2 + (0 * x))");
+    assert_eq!(repr(&env, "if(1 < 2, x, y)"), "x");
+
+    for line in ["x * 1", "1 * x", "x / 1", "x¹"] {
+        assert_eq!(repr(&env, line), "x", "{line}");
+    }
+    assert_eq!(repr(&env, "sin(x) + 0"), "sin(x)");
+    assert_eq!(repr(&env, "0 + sin(x)"), "sin(x)");
+    // Only dimensionless values may lose an added zero, which is otherwise an error
+    assert_eq!(repr(&env, "x [m] + 0"), "((x * 1 [m]) + 0)");
+
+    assert_eq!(repr(&env, "x [km]"), "(x * 1000 [m])");
+    assert_eq!(repr(&env, "x [h]"), "(x * 3600 [s])");
+
+    // Dimensions registered at runtime are named, so that the view parses again
+    env.load_units("extra", "dimension bit\nbyte = 8 bit")
+        .unwrap();
+    assert_eq!(repr(&env, "x [byte]"), "(x * 8 [bit])");
+    let folded = repr(&env, "2 [byte] / 4 [s]");
+    assert_eq!(folded, "4 [s^-1 bit]");
+    assert_eq!(
+        value(&mut env, &folded).dim(),
+        value(&mut env, "1 [bit/s]").dim()
+    );
+
+    assert_eq!(repr(&env, "g(z)"), "(3 * z)");
+    assert_eq!(repr(&env, "g(2)"), "6");
+    assert_eq!(repr(&env, "g(z + 1)"), "g((z + 1))");
+
+    // Errors come up at the same place as in the original node
+    for line in ["x [km] + 0", "g(1 [m]) + 1", "x + ln(0 - 1 [m])"] {
+        let (node, simplified) = simplify(&env, line);
+        let span = |e: NodeError| (e.start, e.end);
+        assert_eq!(
+            simplified.eval(&env, &args).map_err(span).unwrap_err(),
+            node.eval(&env, &args).map_err(span).unwrap_err(),
+            "{line}"
+        );
+    }
+    // except that those within an inlined body are at the call
+    let (node, simplified) = simplify(&env, "2 k(y)");
+    let args = HashMap::from([("y".to_string(), value(&mut env, "1 [m]"))]);
+    assert!(matches!(
+        node.eval(&env, &args),
+        Err(NodeError {
+            content: NodeErrorContent::NestedError(..),
+            ..
+        })
+    ));
+    assert!(matches!(
+        simplified.eval(&env, &args),
+        Err(NodeError {
+            content: NodeErrorContent::ValueError(_),
+            start: 2,
+            end: 6,
+        })
+    ));
+}
+
+#[test]
+fn simplified_nodes_follow_redefined_functions() {
+    let mut env = environment(AngleMode::Dimensionless);
+    assert!(run(&mut env, "g(x) = 3 x").is_ok());
+    let args = HashMap::from([("z".to_string(), Value::Rational(2.into()))]);
+    let simplify = |line: &str| match parse_line(line).unwrap() {
+        Statement::Expression(node) => env.simplify(&node),
+        _ => unreachable!(),
+    };
+    let rational = |value: Result<Value, _>| match value {
+        Ok(Value::Rational(r)) => r,
+        v => panic!("{v:?} is not rational"),
+    };
+
+    let inlined = simplify("g(z)");
+    let folded = simplify("g(2)");
+    let builtin = simplify("sin(0) + z");
+    let unrelated = simplify("z z");
+    assert!(rational(inlined.eval(&env, &args)) == 6.into());
+
+    assert!(run(&mut env, "g(x) = 5 x").is_ok());
+    assert!(run(&mut env, "sin(x) = 1").is_ok());
+    assert!(!inlined.is_current(&env));
+    assert!(!folded.is_current(&env));
+    assert!(!builtin.is_current(&env));
+    assert!(unrelated.is_current(&env));
+    assert!(rational(inlined.eval(&env, &args)) == 10.into());
+    assert!(rational(folded.eval(&env, &args)) == 10.into());
+    assert!(rational(builtin.eval(&env, &args)) == 3.into());
+}
+
+#[test]
+fn bessel_orders_are_bounded() {
+    let mut env = environment(AngleMode::Dimensionless);
+
+    for line in [
+        "besselj(2147483647, 1)",
+        "bessely(1001, 1)",
+        "besseli(0 - 1001, 1 ± 0.1)",
+        "besselk(2147483647, 1 ± 0.1)",
+    ] {
+        assert!(matches!(
+            value_error(&mut env, line),
+            ValueError::OrderOutOfRange(_)
+        ));
+    }
+    for line in ["besselj(1000, 30 ± 0.1)", "besseli(0 - 1000, 1 ± 0.1)"] {
+        assert!(
+            matches!(value(&mut env, line), Value::Quantity(_)),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn declared_units() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let scalar = |value: Value| match value {
+        Value::Quantity(Quantity {
+            value: FloatPlus::Scalar(x),
+            ..
+        }) => x,
+        v => panic!("{v:?} is not a scalar quantity"),
+    };
+
+    assert_eq!(
+        scalar(value(&mut env, "unit furlong = 201.168 [m]")),
+        201.168
+    );
+    assert_eq!(scalar(value(&mut env, "unit fast = [km/h]")), 1. / 3.6);
+    assert!((scalar(value(&mut env, "36 [fast] -> [m/s]")) - 10.).abs() < 1e-12);
+    assert!((scalar(value(&mut env, "1 [kfurlong] -> [m]")) - 201_168.).abs() < 1e-9);
+
+    // Names which already read as a unit, a prefixed unit or a logarithmic unit
+    for line in [
+        "unit furlong = 200 [m]",
+        "unit min = 1 [s]",
+        "unit km = 1000 [m]",
+        "unit ms = 1 [s]",
+        "unit dB = 1",
+    ] {
+        assert!(
+            matches!(
+                run(&mut env, line),
+                Err(NodeErrorContent::UnitExistsError(_))
+            ),
+            "{line}"
+        );
+    }
+    assert_eq!(scalar(value(&mut env, "1 [furlong] -> [m]")), 201.168);
+
+    assert!(matches!(
+        run(&mut env, "unit neg = -1 [m]"),
+        Err(NodeErrorContent::NegativeUnitValue)
+    ));
+    for line in ["unit zero = 0 [m]", "unit unsure = (1 ± 0.1) [m]"] {
+        assert!(
+            matches!(
+                run(&mut env, line),
+                Err(NodeErrorContent::NotPlainUnitValue)
+            ),
+            "{line}"
+        );
+    }
+    assert!(matches!(
+        run(&mut env, "1 [neg]"),
+        Err(NodeErrorContent::UnitNameError(..))
+    ));
+}
+
+#[test]
+fn suggestions() {
+    let mut env = environment(AngleMode::Dimensionless);
+    value(&mut env, "speed = 3");
+    let params = HashMap::new();
+
+    assert_eq!(env.suggest_units("meters")[0], "m");
+    assert_eq!(env.suggest_units("kilometres")[0], "km");
+    assert_eq!(env.suggest_units("secs")[0], "s");
+    assert_eq!(env.suggest_units("Kg")[0], "kg");
+    assert!(env.suggest_units("KM").contains(&"km".to_string()));
+
+    assert_eq!(env.suggest_units("kgg")[0], "kg");
+    assert_eq!(env.suggest_units("mins")[0], "min");
+    assert_eq!(env.suggest_units("kmins")[0], "kmin");
+    assert_eq!(env.suggest_vars("sped", &params), ["speed"]);
+    assert_eq!(env.suggest_funcs("sqr")[0], "sqrt");
+
+    // Short names are a single edit away from most other short names
+    assert!(env.suggest_units("Ω").is_empty());
+    assert!(env.suggest_units("q").is_empty());
+    assert!(env.suggest_vars("x", &params).is_empty());
+    assert!(env.suggest_funcs("fg").is_empty());
+    assert!(!env.suggest_units("mtr").contains(&"msr".to_string()));
+    assert!(env.suggest_vars("xpeed", &params).is_empty());
+
+    assert!(matches!(
+        run(&mut env, "2 [metres]"),
+        Err(NodeErrorContent::UnitNameError(name, similar)) if name == "metres" && similar[0] == "m"
+    ));
+}
+
+#[test]
+fn prefixed_units() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let factor = |env: &Environment, name: &str| env.resolve_unit(name).unwrap().factor;
+    let close = |a: f64, b: f64| (a - b).abs() <= 1e-12 * b.abs();
+
+    assert!(close(factor(&env, "µs"), 1e-6));
+    assert!(close(factor(&env, "kmin"), 6e4));
+    assert!(env.resolve_unit("m").is_some() && env.resolve_unit("xm").is_none());
+
+    // The longest prefix wins, and prefixes of inner scopes hide outer ones
+    let metre = env.resolve_unit("m").unwrap();
+    let am = ConversionValue {
+        factor: 3.,
+        ..metre
+    };
+    env.units.insert("am".into(), am);
+    assert!(close(factor(&env, "dam"), 10.));
+    let mut session = Environment::child(Arc::new(env));
+    session.prefixes.insert("k".into(), 2.);
+    assert!(close(factor(&session, "km"), 2.));
+    assert!(close(factor(&session, "Mm"), 1e6));
+}
+
+#[test]
+fn information_units() {
+    let mut env = environment(AngleMode::Dimensionless);
+    env.units
+        .extend(units::information_units(SIDimension::DIMLESS));
+    env.prefixes.extend(units::binary_prefixes());
+    let scalar = |value: Value| match value {
+        Value::Quantity(Quantity {
+            value: FloatPlus::Scalar(x),
+            ..
+        }) => x,
+        v => panic!("{v:?} is not a scalar quantity"),
+    };
+
+    assert_eq!(scalar(value(&mut env, "1 [KiB] -> [bit]")), 8192.);
+    assert_eq!(scalar(value(&mut env, "1 [kB] -> [B]")), 1000.);
+    assert_eq!(scalar(value(&mut env, "1 [MiB/s] -> [bps]")), 8_388_608.);
+
+    // `dB` is a decibel before it is a prefixed byte, wherever it is looked up
+    assert!(env.resolve_unit("dB").is_none());
+    assert!((scalar(value(&mut env, "3 [dB]")) - 10f64.powf(0.3)).abs() < 1e-12);
+    assert!(matches!(
+        run(&mut env, "f(x [dB]) = x"),
+        Err(NodeErrorContent::DimensionErrors(..))
+    ));
+    assert_eq!(env.resolve_unit("dbit").unwrap().factor, 0.1);
+}
+
+#[test]
+fn complex_branches_outside_real_domain() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let parts = |value: Value| match value {
+        Value::Complex(Complex {
+            real: FloatPlus::Scalar(real),
+            imag: FloatPlus::Scalar(imag),
+            ..
+        }) => (real, imag),
+        v => panic!("{v:?} is not a complex scalar"),
+    };
+    let acosh_2 = 2f64.acosh();
+    let pi = std::f64::consts::PI;
+
+    for (line, expected) in [
+        ("asin(2)", (pi / 2., acosh_2)),
+        ("asin(0 - 2)", (-pi / 2., acosh_2)),
+        ("acos(2)", (0., -acosh_2)),
+        ("atanh(2)", (3f64.ln() / 2., pi / 2.)),
+        ("acosh(0.5)", (0., pi / 3.)),
+        ("ln(0 - 1)", (0., pi)),
+        ("log10(0 - 100)", (2., pi / 10f64.ln())),
+        ("log2(0 - 8)", (3., pi / 2f64.ln())),
+        ("sqrt(0 - 4)", (0., 2.)),
+    ] {
+        let (real, imag) = parts(value(&mut env, line));
+        assert!(
+            (real - expected.0).abs() < 1e-12 && (imag - expected.1).abs() < 1e-12,
+            "{line} is {real} + {imag}i, expected {expected:?}"
+        );
+    }
+    assert!(matches!(value(&mut env, "asin(0.5)"), Value::Quantity(_)));
+    assert!(matches!(value(&mut env, "ln(2)"), Value::Quantity(_)));
+
+    // A vector with any element outside the domain is complex as a whole
+    let x = Value::Quantity(Quantity {
+        value: FloatPlus::Vector([0.5, 2.].into_iter().collect()),
+        derivatives: Default::default(),
+        dim: SIDimension::DIMLESS,
+    });
+    match x.asin().unwrap() {
+        Value::Complex(Complex {
+            real: FloatPlus::Vector(real),
+            imag: FloatPlus::Vector(imag),
+            ..
+        }) => {
+            assert!((real[0] - 0.5f64.asin()).abs() < 1e-12 && imag[0].abs() < 1e-12);
+            assert!((real[1] - pi / 2.).abs() < 1e-12 && (imag[1] - acosh_2).abs() < 1e-12);
+        }
+        v => panic!("asin of [0.5, 2] is {v:?}"),
+    }
+}
+
+#[test]
+fn base_dimensions_registered_at_runtime() {
+    let mut env = environment(AngleMode::Dimensionless);
+    assert_eq!(env.dimensions.index_of("kg"), Some(SIDimension::MASS));
+    assert_eq!(env.dimensions.register("m"), SIDimension::LENGTH);
+
+    env.load_units("extra", "dimension bit\nbyte = 8 bit\ndimension person")
+        .unwrap();
+    let bit = env.dimensions.index_of("bit").unwrap();
+    let person = env.dimensions.index_of("person").unwrap();
+    assert!(bit > SIDimension::SOLID_ANGLE && person > bit);
+
+    let rate = value(&mut env, "3 [byte] / 2 [s] / 4 [person]").dim();
+    assert_eq!(rate.exponent(bit), 1.into());
+    assert_eq!(rate.exponent(person), (-1).into());
+    assert_eq!(env.dimensions.format(&rate), "s^-1 bit person^-1");
+
+    let error = value_error(&mut env, "2 [byte] + 1 [m]");
+    assert_eq!(
+        error.describe(&env.dimensions),
+        "dimensions bit and m are not equal"
+    );
+    // Without the table of the environment only the index is known
+    assert_eq!(
+        error.describe(&DimensionTable::default()),
+        format!("dimensions dim{bit} and m are not equal")
+    );
+}
+
+#[test]
+fn unit_files() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let scalar = |value: Value| match value {
+        Value::Quantity(Quantity {
+            value: FloatPlus::Scalar(x),
+            ..
+        }) => x,
+        v => panic!("{v:?} is not a scalar quantity"),
+    };
+
+    // Definitions may come in any order and use prefixes and aliases from the same file
+    env.load_units(
+        "lengths",
+        "prefix kibi- = 2^10\n\
+         alias Ki- = kibi-\n\
+         alias metre = m  # spelled out\n\
+         mile = 8 furlong\n\
+         furlong = 201.168 metre\n\
+         hertz = 1/s",
+    )
+    .unwrap();
+    assert!((scalar(value(&mut env, "1 [mile] -> [m]")) - 1609.344).abs() < 1e-9);
+    assert_eq!(scalar(value(&mut env, "1 [kibimetre] -> [m]")), 1024.);
+    assert_eq!(scalar(value(&mut env, "1 [Kim] -> [m]")), 1024.);
+    assert_eq!(scalar(value(&mut env, "2 [khertz] * 1 [s]")), 2000.);
+}
+
+#[test]
+fn unit_file_errors() {
+    let error = |source: &str| {
+        let mut env = environment(AngleMode::Dimensionless);
+        let error = env.load_units("defs", source).unwrap_err();
+        assert_eq!(error.file, "defs");
+        (
+            error.line,
+            error.error.reason,
+            error.error.start,
+            error.error.end,
+        )
+    };
+    let owned = |(line, reason, start, end): (usize, &str, usize, usize)| {
+        (line, reason.to_string(), start, end)
+    };
+
+    for (source, expected) in [
+        (
+            "a = 1 m\na = 2 m",
+            (2, "'a' is already defined on line 1", 0, 1),
+        ),
+        ("m = 2 s", (1, "'m' is already a unit", 0, 1)),
+        ("dB = 2", (1, "'dB' is already a unit", 0, 2)),
+        ("prefix k- = 10", (1, "'k' is already a prefix", 7, 9)),
+        (
+            "a = 2 b\nb = 3 a",
+            (2, "cyclic definition a -> b -> a", 6, 7),
+        ),
+        (
+            "alias pa- = pb-\nalias pb- = pa-",
+            (2, "cyclic definition pa -> pb -> pa", 6, 9),
+        ),
+        ("x = 1 m\n  a = 2 zz", (2, "unknown unit 'zz'", 8, 10)),
+        ("alias foo- = zz-", (1, "unknown prefix 'zz'", 6, 10)),
+        ("\n# note\nfurlong = = 3", (3, "unexpected", 10, 11)),
+    ] {
+        assert_eq!(error(source), owned(expected), "{source:?}");
+    }
+
+    // Nothing is loaded from a file with an error
+    let mut env = environment(AngleMode::Dimensionless);
+    assert!(
+        env.load_units("defs", "dimension bit\ngood = 1 m\nbad = 1 zz")
+            .is_err()
+    );
+    assert!(env.resolve_unit("good").is_none());
+    assert!(env.dimensions.index_of("bit").is_none());
+}
+
+#[test]
+fn call_limits() {
+    let mut env = environment(AngleMode::Dimensionless);
+    assert!(run(&mut env, "f(n) = if(n < 1, 0, f(n - 1) + 1)").is_ok());
+    assert!(matches!(value(&mut env, "f(100)"), Value::Rational(r) if r == 100.into()));
+
+    match run(&mut env, "f(300)") {
+        Err(e @ NodeErrorContent::CallLimitError(CallLimit::Depth(200), _)) => {
+            let NodeErrorContent::CallLimitError(_, calls) = &e else {
+                unreachable!()
+            };
+            assert_eq!(calls.len(), 201);
+            assert_eq!(
+                e.describe(&env.dimensions),
+                "call depth limit of 200 reached in f -> f -> f -> … 195 more -> f -> f -> f"
+            );
+        }
+        result => panic!("f(300) gives {result:?}"),
+    }
+
+    env.limits = EvalLimits {
+        max_depth: 1000,
+        fuel: 50,
+    };
+    match run(&mut env, "f(100)") {
+        Err(e @ NodeErrorContent::CallLimitError(CallLimit::Fuel(50), _)) => assert_eq!(
+            e.describe(&env.dimensions),
+            "evaluation limit of 50 steps reached in f -> f -> f -> f -> f -> f"
+        ),
+        result => panic!("f(100) gives {result:?}"),
+    }
+    assert!(matches!(value(&mut env, "f(2)"), Value::Rational(r) if r == 2.into()));
+}
+
+#[test]
+fn unbounded_recursion() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let recursion = |env: &mut Environment, line: &str| match run(env, line) {
+        Err(NodeErrorContent::UnboundedRecursion(chain)) => chain,
+        result => panic!("`{line}` gives {result:?}"),
+    };
+
+    assert_eq!(recursion(&mut env, "g(x) = g(x) + 1"), ["g", "g"]);
+    assert!(run(&mut env, "h(x) = k(x)").is_ok());
+    assert_eq!(recursion(&mut env, "k(x) = 2 h(x)"), ["k", "h", "k"]);
+    assert!(env.get_evaluator("k").is_none());
+
+    // Recursion only reached through a branch may have a base case
+    assert!(run(&mut env, "a(x) = b(x) * 2").is_ok());
+    assert!(run(&mut env, "b(x) = if(x > 0, a(x - 1), 0)").is_ok());
+    assert!(run(&mut env, "c(x) = x > 0 and c(x - 1)").is_ok());
+    assert!(matches!(value(&mut env, "a(3)"), Value::Rational(r) if r == 0.into()));
+    // while the condition itself is always evaluated
+    assert_eq!(recursion(&mut env, "d(x) = if(d(x) > 0, 1, 0)"), ["d", "d"]);
+}
+
+#[test]
+fn looked_up_vectors_share_storage() {
+    let mut env = environment(AngleMode::Dimensionless);
+    let sweep = Quantity {
+        value: FloatPlus::Vector((0..1000).map(f64::from).collect()),
+        derivatives: Default::default(),
+        dim: SIDimension::base(SIDimension::LENGTH),
+    };
+    env.define_const("x", sweep.clone().into());
+    let storage = |value: &Value| match value {
+        Value::Quantity(Quantity {
+            value: FloatPlus::Vector(v),
+            ..
+        }) => v.clone(),
+        v => panic!("{v:?} is not a vector quantity"),
+    };
+
+    let original = storage(&sweep.into());
+    assert!(Arc::ptr_eq(&storage(&value(&mut env, "x")), &original));
+    assert!(run(&mut env, "id(v) = v").is_ok());
+    assert!(Arc::ptr_eq(&storage(&value(&mut env, "id(x)")), &original));
+    let doubled = value(&mut env, "2 x");
+    assert!(!Arc::ptr_eq(&storage(&doubled), &original));
+    assert_eq!(storage(&value(&mut env, "x"))[999], 999.);
+}
@@ -0,0 +1,271 @@
+use super::{ConversionValue, Environment};
+use crate::{
+    parse::{
+        ParseError, UnitFileError,
+        unitfile::{Definition, DefinitionKind, parse_definitions},
+    },
+    value::{DimensionTable, SIDimension},
+};
+use std::collections::HashMap;
+
+struct UnitLoader<'a> {
+    env: &'a Environment,
+    file: &'a str,
+    definitions: &'a [Definition],
+    unit_lines: HashMap<&'a str, usize>,
+    prefix_lines: HashMap<&'a str, usize>,
+    dimensions: DimensionTable,
+    units: HashMap<String, ConversionValue>,
+    prefixes: HashMap<String, f64>,
+    // indices of the definitions currently being resolved, innermost last
+    resolving: Vec<usize>,
+}
+
+impl Environment {
+    /// Loads the unit definition file `source`, with `file` only used to name it in errors.
+    ///
+    /// Definitions may refer to units and prefixes of the environment and to each other in
+    /// any order. On error the environment is left unchanged.
+    pub fn load_units(&mut self, file: &str, source: &str) -> Result<(), UnitFileError> {
+        let definitions = parse_definitions(file, source)?;
+
+        let mut loader = UnitLoader {
+            env: self,
+            file,
+            definitions: &definitions,
+            unit_lines: HashMap::new(),
+            prefix_lines: HashMap::new(),
+            dimensions: self.dimensions.clone(),
+            units: HashMap::new(),
+            prefixes: HashMap::new(),
+            resolving: Vec::new(),
+        };
+        loader.check_duplicates()?;
+        loader.resolve_all()?;
+
+        let UnitLoader {
+            dimensions,
+            units,
+            prefixes,
+            ..
+        } = loader;
+        self.dimensions = dimensions;
+        self.units.extend(units);
+        self.prefixes.extend(prefixes);
+        Ok(())
+    }
+}
+
+impl<'a> UnitLoader<'a> {
+    fn error(
+        &self,
+        definition: &Definition,
+        reason: String,
+        start: usize,
+        end: usize,
+    ) -> UnitFileError {
+        UnitFileError {
+            file: self.file.into(),
+            line: definition.line,
+            error: ParseError { reason, start, end },
+        }
+    }
+
+    fn check_duplicates(&mut self) -> Result<(), UnitFileError> {
+        let definitions = self.definitions;
+        for (index, definition) in definitions.iter().enumerate() {
+            let name = definition.name.as_str();
+            let is_prefix = matches!(
+                definition.kind,
+                DefinitionKind::Prefix(_) | DefinitionKind::PrefixAlias(_)
+            );
+
+            let (lines, existing) = if is_prefix {
+                let existing = self.env.get_prefix(name).map(|_| "a prefix");
+                (&mut self.prefix_lines, existing)
+            } else if self.env.get_unit(name).is_some() || self.env.get_log_unit(name).is_some() {
+                (&mut self.unit_lines, Some("a unit"))
+            } else {
+                (&mut self.unit_lines, None)
+            };
+
+            let reason = match lines.insert(name, index) {
+                Some(first) => format!(
+                    "'{name}' is already defined on line {}",
+                    definitions[first].line
+                ),
+                None => match existing {
+                    Some(kind) => format!("'{name}' is already {kind}"),
+                    None => continue,
+                },
+            };
+
+            return Err(self.error(definition, reason, definition.start, definition.end));
+        }
+
+        Ok(())
+    }
+
+    fn resolve_all(&mut self) -> Result<(), UnitFileError> {
+        // Prefixes are plain numbers, so they are resolved before any unit can need them
+        for index in 0..self.definitions.len() {
+            if let DefinitionKind::Prefix(_) | DefinitionKind::PrefixAlias(_) =
+                self.definitions[index].kind
+            {
+                self.resolve_prefix(index)?;
+            }
+        }
+
+        for index in 0..self.definitions.len() {
+            if let DefinitionKind::Dimension | DefinitionKind::Unit(..) =
+                self.definitions[index].kind
+            {
+                self.resolve_unit(index)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_cycle(&self, index: usize, start: usize, end: usize) -> Result<(), UnitFileError> {
+        let position = match self.resolving.iter().position(|&i| i == index) {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+
+        let chain = self.resolving[position..]
+            .iter()
+            .chain([&index])
+            .map(|&i| self.definitions[i].name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" -> ");
+        let innermost = &self.definitions[*self.resolving.last().unwrap()];
+
+        Err(self.error(innermost, format!("cyclic definition {chain}"), start, end))
+    }
+
+    fn resolve_prefix(&mut self, index: usize) -> Result<f64, UnitFileError> {
+        let definitions = self.definitions;
+        let definition = &definitions[index];
+        if let Some(&factor) = self.prefixes.get(&definition.name) {
+            return Ok(factor);
+        }
+
+        let factor = match &definition.kind {
+            &DefinitionKind::Prefix(factor) => factor,
+            DefinitionKind::PrefixAlias(target) => {
+                let (start, end) = (definition.start, definition.end);
+                match self.prefix_lines.get(target.as_str()) {
+                    Some(&target_index) => {
+                        self.resolving.push(index);
+                        self.check_cycle(target_index, start, end)?;
+                        let factor = self.resolve_prefix(target_index)?;
+                        self.resolving.pop();
+                        factor
+                    }
+                    None => match self.env.get_prefix(target) {
+                        Some(factor) => factor,
+                        None => {
+                            let reason = format!("unknown prefix '{target}'");
+                            return Err(self.error(definition, reason, start, end));
+                        }
+                    },
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        self.prefixes.insert(definition.name.clone(), factor);
+        Ok(factor)
+    }
+
+    fn resolve_unit(&mut self, index: usize) -> Result<ConversionValue, UnitFileError> {
+        let definitions = self.definitions;
+        let definition = &definitions[index];
+        if let Some(conversion) = self.units.get(&definition.name) {
+            return Ok(conversion.clone());
+        }
+
+        let conversion = match &definition.kind {
+            DefinitionKind::Dimension => ConversionValue {
+                factor: 1.,
+                dim: SIDimension::base(self.dimensions.register(&definition.name)),
+            },
+            DefinitionKind::Unit(units) => {
+                self.resolving.push(index);
+
+                let mut result = ConversionValue {
+                    factor: units.factor,
+                    dim: SIDimension::DIMLESS,
+                };
+                for term in &units.terms {
+                    let conversion = match self.lookup(&term.unit, term.start, term.end)? {
+                        Some(conversion) => conversion,
+                        None => {
+                            let reason = format!("unknown unit '{}'", term.unit);
+                            return Err(self.error(definition, reason, term.start, term.end));
+                        }
+                    };
+
+                    result.factor *= conversion.factor.powf(term.power.into());
+                    result.dim = result.dim.mul(&conversion.dim.pow(term.power));
+                }
+
+                self.resolving.pop();
+                result
+            }
+            _ => unreachable!(),
+        };
+
+        self.units
+            .insert(definition.name.clone(), conversion.clone());
+        Ok(conversion)
+    }
+
+    // Same rules as `Environment::resolve_unit`, with the units and prefixes of the file
+    // taking part. Unit definitions of the file are resolved on demand.
+    fn lookup(
+        &mut self,
+        name: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Option<ConversionValue>, UnitFileError> {
+        if let Some(conversion) = self.lookup_unprefixed(name, start, end)? {
+            return Ok(Some(conversion));
+        }
+
+        for (len, _) in name.char_indices().rev().filter(|&(len, _)| len > 0) {
+            let prefix = &name[..len];
+            let prefix_factor = match self.prefixes.get(prefix) {
+                Some(&factor) => factor,
+                None => match self.env.get_prefix(prefix) {
+                    Some(factor) => factor,
+                    None => continue,
+                },
+            };
+            if let Some(conversion) = self.lookup_unprefixed(&name[len..], start, end)? {
+                return Ok(Some(ConversionValue {
+                    factor: prefix_factor * conversion.factor,
+                    dim: conversion.dim,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn lookup_unprefixed(
+        &mut self,
+        name: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Option<ConversionValue>, UnitFileError> {
+        match self.unit_lines.get(name) {
+            Some(&index) => {
+                self.check_cycle(index, start, end)?;
+                self.resolve_unit(index).map(Some)
+            }
+            None => Ok(self.env.get_unit(name).cloned()),
+        }
+    }
+}
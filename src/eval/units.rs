@@ -0,0 +1,280 @@
+use super::{ConversionValue, Environment, LogConversion, UnitExpr, UnitTerm};
+use crate::{
+    f64plus::FloatPlus,
+    rational::Rational,
+    value::{Derivatives, Quantity, SIDimension, Value, ValueError},
+};
+use std::{
+    f64::consts::{E, PI},
+    ops::Mul,
+};
+
+pub fn radian() -> Quantity {
+    Quantity {
+        value: FloatPlus::Scalar(1.),
+        derivatives: Derivatives::new(),
+        dim: SIDimension::base(SIDimension::ANGLE),
+    }
+}
+
+impl UnitExpr {
+    pub const ONE: Self = UnitExpr {
+        factor: 1.,
+        terms: Vec::new(),
+    };
+
+    pub fn number(factor: f64) -> Self {
+        UnitExpr {
+            factor,
+            terms: Vec::new(),
+        }
+    }
+
+    /// The units raised to `power`, or `None` if an exponent no longer fits a `Rational`.
+    pub fn checked_pow(self, power: Rational) -> Option<Self> {
+        Some(UnitExpr {
+            factor: self.factor.powf(power.into()),
+            terms: self
+                .terms
+                .into_iter()
+                .map(|term| {
+                    Some(UnitTerm {
+                        power: term.power.checked_mul(power)?,
+                        ..term
+                    })
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+impl Mul for UnitExpr {
+    type Output = Self;
+
+    fn mul(mut self, other: Self) -> Self {
+        self.factor *= other.factor;
+        self.terms.extend(other.terms);
+        self
+    }
+}
+
+fn unit(name: &str, factor: f64, dim: SIDimension) -> (String, ConversionValue) {
+    (name.into(), ConversionValue { factor, dim })
+}
+
+pub fn angle_units() -> Vec<(String, ConversionValue)> {
+    let angle = SIDimension::base(SIDimension::ANGLE);
+
+    vec![
+        unit("rad", 1., angle.clone()),
+        unit("deg", PI / 180., angle.clone()),
+        unit("°", PI / 180., angle.clone()),
+        unit("arcmin", PI / 10_800., angle.clone()),
+        unit("′", PI / 10_800., angle.clone()),
+        unit("arcsec", PI / 648_000., angle.clone()),
+        unit("″", PI / 648_000., angle.clone()),
+        unit("grad", PI / 200., angle.clone()),
+        unit("cycle", 2. * PI, angle.clone()),
+        unit("turn", 2. * PI, angle),
+        unit("sr", 1., SIDimension::base(SIDimension::SOLID_ANGLE)),
+    ]
+}
+
+fn scalar(value: f64, dim: SIDimension) -> Value {
+    Quantity {
+        value: FloatPlus::Scalar(value),
+        derivatives: Derivatives::new(),
+        dim,
+    }
+    .into()
+}
+
+fn compound_dim(exponents: &[(usize, i32)]) -> SIDimension {
+    exponents
+        .iter()
+        .fold(SIDimension::DIMLESS, |dim, &(index, power)| {
+            dim.mul(&SIDimension::base(index).pow(power.into()))
+        })
+}
+
+fn log_unit(
+    name: &str,
+    reference: f64,
+    dim: SIDimension,
+    base: f64,
+    scale: f64,
+) -> (String, LogConversion) {
+    (
+        name.into(),
+        LogConversion {
+            reference,
+            dim,
+            base,
+            scale,
+        },
+    )
+}
+
+// Quantities given in logarithmic units are converted to linear SI values straight away,
+// so adding two of them sums their powers or fields. Gains in dB are applied with `*`,
+// e.g. -30 [dBm] * 3 [dB] is about 2 µW. Dimensionless dB and Np both stand for power
+// ratios, so that 1 [Np] -> [dB] gives 20/ln(10).
+pub fn log_units() -> Vec<(String, LogConversion)> {
+    let watt = compound_dim(&[
+        (SIDimension::MASS, 1),
+        (SIDimension::LENGTH, 2),
+        (SIDimension::TIME, -3),
+    ]);
+    let volt = watt.mul(&SIDimension::base(SIDimension::CURRENT).reciprocal());
+    let pascal = compound_dim(&[
+        (SIDimension::MASS, 1),
+        (SIDimension::LENGTH, -1),
+        (SIDimension::TIME, -2),
+    ]);
+    let molar = compound_dim(&[(SIDimension::QUANTITY, 1), (SIDimension::LENGTH, -3)]);
+
+    vec![
+        log_unit("dB", 1., SIDimension::DIMLESS, 10., 10.),
+        log_unit("dBm", 1e-3, watt.clone(), 10., 10.),
+        log_unit("dBW", 1., watt, 10., 10.),
+        log_unit("dBV", 1., volt.clone(), 10., 20.),
+        log_unit("dBu", 0.6f64.sqrt(), volt.clone(), 10., 20.),
+        log_unit("dBµV", 1e-6, volt.clone(), 10., 20.),
+        log_unit("dBuV", 1e-6, volt, 10., 20.),
+        log_unit("dBSPL", 20e-6, pascal, 10., 20.),
+        log_unit("Np", 1., SIDimension::DIMLESS, E, 0.5),
+        log_unit("pH", 1e3, molar, 10., -1.),
+    ]
+}
+
+impl LogConversion {
+    /// The linear SI value of `x` in this unit.
+    pub fn to_linear(&self, x: &Value) -> Result<Value, ValueError> {
+        x.mul(&scalar(self.base.ln() / self.scale, SIDimension::DIMLESS))?
+            .exp()?
+            .mul(&scalar(self.reference, self.dim.clone()))
+    }
+
+    /// The amount of this unit equal to the linear SI value `v`.
+    pub fn from_linear(&self, v: &Value) -> Result<Value, ValueError> {
+        let ratio = v.div(&scalar(self.reference, self.dim.clone()))?;
+        if !ratio.dimless() {
+            return Err(ValueError::UnequalDimensions(v.dim(), self.dim.clone()));
+        }
+
+        ratio
+            .natlog()?
+            .mul(&scalar(self.scale / self.base.ln(), SIDimension::DIMLESS))
+    }
+}
+
+pub fn information_units(information: SIDimension) -> Vec<(String, ConversionValue)> {
+    let per_second = SIDimension::base(SIDimension::TIME).reciprocal();
+
+    vec![
+        unit("bit", 1., information.clone()),
+        unit("nibble", 4., information.clone()),
+        unit("B", 8., information.clone()),
+        unit("byte", 8., information.clone()),
+        unit("bps", 1., information.mul(&per_second)),
+        unit("baud", 1., per_second.clone()),
+        unit("Bd", 1., per_second),
+    ]
+}
+
+pub fn si_prefixes() -> Vec<(String, f64)> {
+    [
+        ("Q", 1e30),
+        ("R", 1e27),
+        ("Y", 1e24),
+        ("Z", 1e21),
+        ("E", 1e18),
+        ("P", 1e15),
+        ("T", 1e12),
+        ("G", 1e9),
+        ("M", 1e6),
+        ("k", 1e3),
+        ("h", 1e2),
+        ("da", 1e1),
+        ("d", 1e-1),
+        ("c", 1e-2),
+        ("m", 1e-3),
+        ("µ", 1e-6),
+        ("μ", 1e-6),
+        ("u", 1e-6),
+        ("n", 1e-9),
+        ("p", 1e-12),
+        ("f", 1e-15),
+        ("a", 1e-18),
+        ("z", 1e-21),
+        ("y", 1e-24),
+        ("r", 1e-27),
+        ("q", 1e-30),
+    ]
+    .map(|(name, factor)| (name.into(), factor))
+    .to_vec()
+}
+
+pub fn binary_prefixes() -> Vec<(String, f64)> {
+    ["Ki", "Mi", "Gi", "Ti", "Pi", "Ei", "Zi", "Yi"]
+        .iter()
+        .zip(1..)
+        .map(|(&name, power)| (name.into(), 1024f64.powi(power)))
+        .collect()
+}
+
+/// Splits `value` into whole amounts of each factor but the last, which takes what remains.
+///
+/// Amounts within rounding error of the next whole number are carried, so that 2 h given in
+/// seconds becomes 2 h 0 min 0 s rather than 1 h 59 min 60 s. Negative values have every
+/// amount negated. The factors are expected to be in decreasing order, and there are no
+/// amounts without any.
+pub fn decompose(value: f64, factors: &[f64]) -> Vec<f64> {
+    let (&last_factor, whole_factors) = match factors.split_last() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let tolerance = 64. * f64::EPSILON * value.abs();
+    let mut remainder = value.abs();
+    let mut amounts = Vec::with_capacity(factors.len());
+
+    for factor in whole_factors {
+        let amount = ((remainder + tolerance) / factor).floor();
+        remainder = (remainder - amount * factor).max(0.);
+        amounts.push(amount.copysign(value));
+    }
+
+    let last_amount = remainder / last_factor;
+    amounts.push(last_amount.copysign(value));
+    amounts
+}
+
+impl Environment {
+    /// Looks up a unit by name, falling back to a prefix followed by a unit name.
+    ///
+    /// An exact unit name always wins, and among prefixed readings the longest prefix is
+    /// used, so `dam` is a decametre rather than a deci-`am`. A logarithmic unit wins over
+    /// prefixed readings, so `dB` is always a decibel rather than a deci-byte.
+    pub fn resolve_unit(&self, name: &str) -> Option<ConversionValue> {
+        if let Some(conversion) = self.get_unit(name) {
+            return Some(conversion.clone());
+        }
+        if self.get_log_unit(name).is_some() {
+            return None;
+        }
+
+        // Longest prefix first, trying each split of the name so that no table of the
+        // prefixes of every scope is built
+        name.char_indices()
+            .rev()
+            .filter(|&(i, _)| i > 0)
+            .find_map(|(i, _)| {
+                let prefix_factor = self.get_prefix(&name[..i])?;
+                let conversion = self.get_unit(&name[i..])?;
+                Some(ConversionValue {
+                    factor: prefix_factor * conversion.factor,
+                    dim: conversion.dim.clone(),
+                })
+            })
+    }
+}
@@ -1,32 +1,172 @@
 use FloatPlus::{Scalar, Vector};
+use std::{cell::Cell, sync::Arc};
 
+/// A float, or a vector of them such as the points of a sweep. Vectors are shared when
+/// cloned and only copied when changed while shared.
 #[derive(Clone, Debug)]
 pub enum FloatPlus {
     Scalar(f64),
-    Vector(Vec<f64>),
+    Vector(Arc<[f64]>),
+}
+
+/// How operations on long vectors are split across threads. Each thread computes the
+/// elements of its own part exactly as a single thread would, so the results do not depend
+/// on the split.
+#[derive(Copy, Clone, Debug)]
+pub struct Parallelism {
+    /// Most threads working on one operation, where 1 keeps everything on the calling thread
+    pub threads: usize,
+    /// Fewest elements for which an operation is split up, as starting threads costs more
+    /// than it saves on shorter vectors
+    pub min_len: usize,
+}
+
+impl Parallelism {
+    pub const SERIAL: Self = Self {
+        threads: 1,
+        min_len: usize::MAX,
+    };
+}
+
+impl Default for Parallelism {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, usize::from),
+            min_len: 1 << 17,
+        }
+    }
+}
+
+thread_local! {
+    // Set while evaluating in an environment, vector operations are serial otherwise
+    static PARALLELISM: Cell<Parallelism> = const { Cell::new(Parallelism::SERIAL) };
+}
+
+struct RestoreParallelism(Parallelism);
+
+impl Drop for RestoreParallelism {
+    fn drop(&mut self) {
+        PARALLELISM.set(self.0);
+    }
+}
+
+/// Runs `f` with the vector operations it does on this thread split up as given.
+pub fn with_parallelism<R>(parallelism: Parallelism, f: impl FnOnce() -> R) -> R {
+    let _restore = RestoreParallelism(PARALLELISM.replace(parallelism));
+    f()
+}
+
+// Length of the part of a vector given to each thread, if it is long enough to be split.
+// Empty vectors never are, as there would be no parts.
+fn part_len(len: usize) -> Option<usize> {
+    let Parallelism { threads, min_len } = PARALLELISM.get();
+    if threads > 1 && len > 0 && len >= min_len {
+        Some(len.div_ceil(threads))
+    } else {
+        None
+    }
+}
+
+// Calls `f` with each part of `elements` along with the index where the part starts, on
+// separate threads when there are enough elements
+fn for_each_part<F>(elements: &mut [f64], f: F)
+where
+    F: Fn(usize, &mut [f64]) + Sync,
+{
+    let part_len = match part_len(elements.len()) {
+        Some(part_len) => part_len,
+        None => return f(0, elements),
+    };
+
+    std::thread::scope(|scope| {
+        for (i, part) in elements.chunks_mut(part_len).enumerate() {
+            let f = &f;
+            scope.spawn(move || f(i * part_len, part));
+        }
+    });
+}
+
+// A vector of `len` elements, where `f` gives those from a start up to an end index
+fn collect_parts<F, I>(len: usize, f: F) -> FloatPlus
+where
+    F: Fn(usize, usize) -> I + Sync,
+    I: Iterator<Item = f64>,
+{
+    if part_len(len).is_none() {
+        return Vector(f(0, len).collect());
+    }
+
+    let mut elements = std::iter::repeat_n(0., len).collect::<Arc<[f64]>>();
+    for_each_part(Arc::get_mut(&mut elements).unwrap(), |start, part| {
+        let end = start + part.len();
+        for (y, x) in part.iter_mut().zip(f(start, end)) {
+            *y = x;
+        }
+    });
+    Vector(elements)
+}
+
+fn element(x: &FloatPlus, i: usize) -> f64 {
+    match x {
+        Scalar(s) => *s,
+        Vector(v) => v[i],
+    }
 }
 
 fn apply_unary_op<F>(val: &FloatPlus, op: F) -> FloatPlus
 where
-    F: Fn(f64) -> f64,
+    F: Fn(f64) -> f64 + Sync,
 {
     match val {
         Scalar(x) => Scalar(op(*x)),
-        Vector(vx) => Vector(vx.into_iter().map(|x| op(*x)).collect()),
+        Vector(vx) => collect_parts(vx.len(), |start, end| vx[start..end].iter().map(|&x| op(x))),
     }
 }
 
 fn unchecked_apply_binary_op<F>(lhs: &FloatPlus, rhs: &FloatPlus, op: F) -> FloatPlus
 where
-    F: Fn(f64, f64) -> f64,
+    F: Fn(f64, f64) -> f64 + Sync,
 {
+    let op = &op;
     match (lhs, rhs) {
         (&Scalar(l), &Scalar(r)) => Scalar(op(l, r)),
-        (Vector(vl), &Scalar(r)) => Vector(vl.into_iter().map(|&l| op(l, r)).collect()),
-        (&Scalar(l), Vector(vr)) => Vector(vr.into_iter().map(|&r| op(l, r)).collect()),
-        (Vector(vl), Vector(vr)) => {
-            Vector(vl.into_iter().zip(vr).map(|(&l, &r)| op(l, r)).collect())
-        }
+        (Vector(vl), &Scalar(r)) => collect_parts(vl.len(), |start, end| {
+            vl[start..end].iter().map(move |&l| op(l, r))
+        }),
+        (&Scalar(l), Vector(vr)) => collect_parts(vr.len(), |start, end| {
+            vr[start..end].iter().map(move |&r| op(l, r))
+        }),
+        (Vector(vl), Vector(vr)) => collect_parts(vl.len().min(vr.len()), |start, end| {
+            let pairs = vl[start..end].iter().zip(&vr[start..end]);
+            pairs.map(|(&l, &r)| op(l, r))
+        }),
+    }
+}
+
+// Like the operations above over any number of operands, where vectors of different
+// lengths are cut to the shortest
+fn unchecked_apply_fused_op<const N: usize, F>(operands: [&FloatPlus; N], op: F) -> FloatPlus
+where
+    F: Fn([f64; N]) -> f64 + Sync,
+{
+    let len = operands
+        .iter()
+        .filter_map(|x| match x {
+            Scalar(_) => None,
+            Vector(v) => Some(v.len()),
+        })
+        .min();
+    // Scalars are read as a vector of one element with a step of zero
+    let strided = operands.map(|x| match x {
+        Scalar(s) => (std::slice::from_ref(s), 0),
+        Vector(v) => (&v[..], 1),
+    });
+
+    match len {
+        None => Scalar(op(strided.map(|(elements, _)| elements[0]))),
+        Some(len) => collect_parts(len, |start, end| {
+            (start..end).map(|i| op(strided.map(|(elements, step)| elements[i * step])))
+        }),
     }
 }
 
@@ -37,7 +177,7 @@ where
     fn from(value: T) -> Self {
         match value.as_ref() {
             &[x] => Self::Scalar(x),
-            v => Self::Vector(v.iter().cloned().collect()),
+            v => Self::Vector(v.into()),
         }
     }
 }
@@ -103,15 +243,173 @@ impl FloatPlus {
 
     pub fn apply_func<F>(&self, f: F) -> Self
     where
-        F: Fn(f64) -> f64,
+        F: Fn(f64) -> f64 + Sync,
     {
         apply_unary_op(self, &f)
     }
 
     pub fn apply_binary_func<F>(&self, other: &Self, f: F) -> Self
     where
-        F: Fn(f64, f64) -> f64,
+        F: Fn(f64, f64) -> f64 + Sync,
     {
         unchecked_apply_binary_op(self, other, f)
     }
+
+    /// `f` of the elements of all the operands at once, in a single pass without vectors
+    /// for the intermediate results. Gives the same floats as the steps done one by one.
+    pub fn apply_fused_func<const N: usize, F>(operands: [&Self; N], f: F) -> Self
+    where
+        F: Fn([f64; N]) -> f64 + Sync,
+    {
+        unchecked_apply_fused_op(operands, f)
+    }
+
+    /// Like `apply_func`, but reusing the vector when it is not shared.
+    pub fn apply_func_in_place<F>(&mut self, f: F)
+    where
+        F: Fn(f64) -> f64 + Sync,
+    {
+        match self {
+            Scalar(x) => *x = f(*x),
+            Vector(v) => match Arc::get_mut(v) {
+                Some(elements) => for_each_part(elements, |_, part| {
+                    for x in part {
+                        *x = f(*x);
+                    }
+                }),
+                None => *self = apply_unary_op(self, f),
+            },
+        }
+    }
+
+    /// Like `apply_binary_func`, but reusing the vector of `self` when it is not shared and
+    /// already has the length of the result.
+    pub fn apply_binary_func_in_place<F>(&mut self, other: &Self, f: F)
+    where
+        F: Fn(f64, f64) -> f64 + Sync,
+    {
+        match (&mut *self, other) {
+            (Scalar(l), &Scalar(r)) => *l = f(*l, r),
+            (Vector(vl), Scalar(_) | Vector(_)) if other.len_at_least(vl.len()) => {
+                match Arc::get_mut(vl) {
+                    Some(elements) => for_each_part(elements, |start, part| {
+                        for (i, l) in (start..).zip(part.iter_mut()) {
+                            *l = f(*l, element(other, i));
+                        }
+                    }),
+                    None => *self = unchecked_apply_binary_op(self, other, f),
+                }
+            }
+            _ => *self = unchecked_apply_binary_op(self, other, f),
+        }
+    }
+
+    fn len_at_least(&self, len: usize) -> bool {
+        match self {
+            Scalar(_) => true,
+            Vector(v) => v.len() >= len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FloatPlus, Parallelism, with_parallelism};
+    use std::sync::Arc;
+
+    fn storage(x: &FloatPlus) -> &Arc<[f64]> {
+        match x {
+            FloatPlus::Vector(v) => v,
+            FloatPlus::Scalar(s) => panic!("{s} is not a vector"),
+        }
+    }
+
+    #[test]
+    fn clones_share_until_changed() {
+        let x = FloatPlus::Vector([1., 2., 3.].into_iter().collect());
+        let mut y = x.clone();
+        assert!(Arc::ptr_eq(storage(&x), storage(&y)));
+
+        // Changing a shared vector copies it, leaving the other clone as it was
+        y.apply_func_in_place(|a| a * 10.);
+        assert!(!Arc::ptr_eq(storage(&x), storage(&y)));
+        assert_eq!(storage(&x)[..], [1., 2., 3.]);
+        assert_eq!(storage(&y)[..], [10., 20., 30.]);
+
+        // while one no longer shared is changed where it is
+        let before = Arc::as_ptr(storage(&y));
+        y.apply_func_in_place(|a| a + 1.);
+        y.apply_binary_func_in_place(&x, |a, b| a - b);
+        assert_eq!(Arc::as_ptr(storage(&y)), before);
+        assert_eq!(storage(&y)[..], [10., 19., 28.]);
+
+        let mut z = x.clone();
+        z.apply_binary_func_in_place(&FloatPlus::Scalar(1.), |a, b| a + b);
+        assert!(!Arc::ptr_eq(storage(&x), storage(&z)));
+        assert_eq!(storage(&x)[..], [1., 2., 3.]);
+        assert_eq!(storage(&z)[..], [2., 3., 4.]);
+    }
+
+    #[test]
+    fn in_place_ops_which_change_the_shape() {
+        // A scalar combined with a vector becomes one
+        let mut s = FloatPlus::Scalar(1.);
+        let x = FloatPlus::Vector([1., 2.].into_iter().collect());
+        s.apply_binary_func_in_place(&x, |a, b| a + b);
+        assert_eq!(storage(&s)[..], [2., 3.]);
+        assert!(!Arc::ptr_eq(storage(&s), storage(&x)));
+    }
+
+    // Elements of every operation on vectors of each length, which may be empty
+    fn results() -> Vec<Vec<f64>> {
+        let mut results = Vec::new();
+        for len in [0, 1, 2, 3, 7, 100] {
+            let x = FloatPlus::Vector((0..len).map(|i| i as f64 + 0.5).collect());
+            let y = FloatPlus::Vector((0..len).map(|i| 2. - i as f64).collect());
+            let mut in_place = x.clone();
+            in_place.apply_binary_func_in_place(&y, |a, b| a * b + 1.);
+            in_place.apply_func_in_place(f64::sin);
+
+            for result in [
+                x.add(&y),
+                x.mul(&FloatPlus::Scalar(3.)),
+                x.div(&y),
+                x.square(),
+                x.apply_func(f64::ln),
+                FloatPlus::apply_fused_func([&x, &y, &x], |[a, b, c]| a * b - c),
+                in_place,
+            ] {
+                match result {
+                    FloatPlus::Vector(v) => results.push(v.to_vec()),
+                    FloatPlus::Scalar(s) => panic!("a vector of {len} gives the scalar {s}"),
+                }
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn threads_give_the_same_results() {
+        let serial = with_parallelism(
+            Parallelism {
+                threads: 1,
+                min_len: 0,
+            },
+            results,
+        );
+        for min_len in [0, 1] {
+            let parallel = with_parallelism(
+                Parallelism {
+                    threads: 4,
+                    min_len,
+                },
+                results,
+            );
+            assert_eq!(
+                format!("{serial:?}"),
+                format!("{parallel:?}"),
+                "min_len {min_len}"
+            );
+        }
+    }
 }
@@ -0,0 +1,7 @@
+pub mod eval;
+pub mod f64plus;
+pub mod parse;
+pub mod rational;
+pub mod special;
+pub mod utils;
+pub mod value;
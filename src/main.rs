@@ -1,18 +1,12 @@
+use physcaulc::{eval, parse, rational, value};
 use std::collections::HashMap;
 
-pub mod eval;
-pub mod f64plus;
-pub mod parse;
-pub mod rational;
-pub mod utils;
-pub mod value;
-
 fn main() {
     let s = "1[kg]";
 
     // dbg!(parse::lex(s));
 
-    let result = parse::lex(s).and_then(parse::parse);
+    let result = parse::parse_line(s);
 
     // dbg!(result);
 
@@ -25,26 +19,44 @@ fn main() {
     //     }
     // }
 
-    let env = eval::Environment {
+    let mut env = eval::Environment {
         consts: HashMap::from([("x".into(), rational::Rational::new(2, 3).into())]),
+        formulas: HashMap::new(),
         evaluators: HashMap::new(),
+        prefixes: HashMap::new(),
+        log_units: HashMap::from_iter(eval::units::log_units()),
         units: HashMap::from([(
             "kg".into(),
             eval::ConversionValue {
                 factor: 1.,
-                dim: value::SIDimension {
-                    mass: 1.into(),
-                    ..Default::default()
-                },
+                dim: value::SIDimension::base(value::SIDimension::MASS),
             },
         )]),
+        dimensions: value::DimensionTable::default(),
+        angle_mode: eval::AngleMode::Dimensionless,
+        limits: eval::EvalLimits::default(),
+        parallelism: Default::default(),
+        sources: Default::default(),
+        parent: None,
     };
+    env.units.extend(eval::units::angle_units());
+    env.units.extend(eval::units::information_units(
+        value::SIDimension::DIMLESS,
+    ));
+    env.prefixes.extend(eval::units::si_prefixes());
+    env.prefixes.extend(eval::units::binary_prefixes());
     let args = HashMap::<String, value::Value>::new();
 
     match result {
-        Ok(node) => {
-            dbg!(node.eval(&env, &args));
-        }
+        Ok(statement) => match statement.execute(&mut env, &args) {
+            Ok((value, warning)) => {
+                if let Some(warning) = warning {
+                    println!("warning: {}", warning.describe());
+                }
+                dbg!(value);
+            }
+            Err(e) => println!("error: {}", e.content.describe(&env.dimensions)),
+        },
         Err(e) => {
             dbg!(e);
         }
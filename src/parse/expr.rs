@@ -1,31 +1,110 @@
-use super::{ParseError, Token, TokenKind, curr_token, expect, step_token};
+use super::units::{parse_unit_groups, parse_units, postfix_unit};
+use super::{ParseError, Token, TokenKind, curr_token, expect, optional, step_token};
 use crate::{
-    eval::{BinaryOp, Node, NodeContent},
+    eval::{BinaryOp, Node, NodeContent, UnaryOp, UncertaintySource},
     rational::Rational,
+    value::{Comparison, Quantity},
 };
 
-pub fn expr(tokens: &Vec<Token>, position: &mut usize) -> Result<Node, ParseError> {
-    series(tokens, position, 3)
+const CONVERSION_LEVEL: usize = 4;
+const COMPARISON_LEVEL: usize = 5;
+const LOOSEST_LEVEL: usize = 7;
+
+pub fn expr(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    series(tokens, position, LOOSEST_LEVEL)
+}
+
+// A sum optionally followed by units to convert it to, which binds looser than arithmetic
+// but tighter than comparisons
+fn conversion(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    let inner = series(tokens, position, CONVERSION_LEVEL - 1)?;
+
+    let arrow_token = curr_token(tokens, position);
+    if arrow_token.kind != TokenKind::Compound("->") {
+        return Ok(inner);
+    }
+    step_token(tokens, position);
+
+    if curr_token(tokens, position).kind != TokenKind::Symbol('[') {
+        let curr = curr_token(tokens, position);
+        return Err(ParseError {
+            reason: "expected units to convert to".into(),
+            start: curr.start,
+            end: curr.end,
+        });
+    }
+
+    let mut unit_groups = parse_unit_groups(tokens, position)?;
+    while curr_token(tokens, position).kind == TokenKind::Symbol('[') {
+        unit_groups.extend(parse_unit_groups(tokens, position)?);
+    }
+
+    // Several groups like -> [h:min:s] or -> [h] [min] [s] decompose the value into a
+    // mixed unit
+    let op = if unit_groups.len() == 1 {
+        UnaryOp::Convert(unit_groups.pop().unwrap())
+    } else {
+        UnaryOp::ConvertMixed(unit_groups)
+    };
+
+    Ok(Node {
+        content: NodeContent::Unary(op, Box::new(inner)),
+        start: arrow_token.start,
+        end: arrow_token.end,
+    })
 }
 
-fn get_binary_op_info(symbol: char) -> Option<(BinaryOp, usize)> {
-    match symbol {
-        '*' => Some((BinaryOp::Mul, 1)),
-        ':' => Some((BinaryOp::Div, 1)),
-        '/' => Some((BinaryOp::Div, 2)),
-        '+' => Some((BinaryOp::Add, 3)),
-        '-' => Some((BinaryOp::Sub, 3)),
+fn get_binary_op_info(kind: &TokenKind) -> Option<(BinaryOp, usize)> {
+    let compare = |comparison| Some((BinaryOp::Compare(comparison), COMPARISON_LEVEL));
+
+    match kind {
+        TokenKind::Symbol('*') => Some((BinaryOp::Mul, 1)),
+        TokenKind::Symbol(':') => Some((BinaryOp::Div, 1)),
+        TokenKind::Symbol('/') => Some((BinaryOp::Div, 2)),
+        TokenKind::Symbol('+') => Some((BinaryOp::Add, 3)),
+        TokenKind::Symbol('-') => Some((BinaryOp::Sub, 3)),
+        TokenKind::Symbol('<') => compare(Comparison::Less),
+        TokenKind::Compound("<=") => compare(Comparison::LessEqual),
+        TokenKind::Symbol('>') => compare(Comparison::Greater),
+        TokenKind::Compound(">=") => compare(Comparison::GreaterEqual),
+        TokenKind::Compound("==") => compare(Comparison::Equal),
+        TokenKind::Compound("!=") => compare(Comparison::NotEqual),
+        TokenKind::Symbol('≈') | TokenKind::Compound("~=") => compare(Comparison::Approx),
+        TokenKind::Word(w) if w == "and" => Some((BinaryOp::And, 6)),
+        TokenKind::Word(w) if w == "or" => Some((BinaryOp::Or, 7)),
         _ => None,
     }
 }
 
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "and" | "or" | "not")
+}
+
+/// Parses binary operators from the loosest `prec_level` down. Below all of the levels of
+/// `get_binary_op_info` is implicit multiplication, so `1/2 pi` is 1/(2 pi) and `a:2b` is
+/// a/(2b). A '±' is at the same level as '+' and '-', and a `not` applies to a whole
+/// comparison.
 pub fn series(
-    tokens: &Vec<Token>,
+    tokens: &[Token],
     position: &mut usize,
     prec_level: usize,
 ) -> Result<Node, ParseError> {
     if prec_level == 0 {
-        return term(tokens, position);
+        return implicit_product(tokens, position);
+    }
+    if prec_level == CONVERSION_LEVEL {
+        return conversion(tokens, position);
+    }
+
+    let not_token = curr_token(tokens, position);
+    if prec_level == COMPARISON_LEVEL && not_token.kind == TokenKind::Word("not".into()) {
+        step_token(tokens, position);
+        let operand = series(tokens, position, prec_level)?;
+        return Ok(Node {
+            content: NodeContent::Unary(UnaryOp::Not, Box::new(operand)),
+            start: not_token.start,
+            end: not_token.end,
+        });
     }
 
     let mut result = series(tokens, position, prec_level - 1)?;
@@ -33,12 +112,22 @@ pub fn series(
     loop {
         let op_token = curr_token(tokens, position);
 
-        let (op, level) = match op_token.kind {
-            TokenKind::Symbol(c) => match get_binary_op_info(c) {
-                Some(info) => info,
-                None => return Ok(result),
-            },
-            _ => return Ok(result),
+        if prec_level == 3 && op_token.kind == TokenKind::Symbol('±') {
+            step_token(tokens, position);
+            let sigma = series(tokens, position, prec_level - 1)?;
+            // Every ± written is a separate source of uncertainty
+            let source = UncertaintySource::new(format!("{} ± {}", result.repr(), sigma.repr()));
+            result = Node {
+                content: NodeContent::Uncertain(Box::new(result), Box::new(sigma), source),
+                start: op_token.start,
+                end: op_token.end,
+            };
+            continue;
+        }
+
+        let (op, level) = match get_binary_op_info(&op_token.kind) {
+            Some(info) => info,
+            None => return Ok(result),
         };
         if level != prec_level {
             return Ok(result);
@@ -56,27 +145,152 @@ pub fn series(
     }
 }
 
-pub fn term(tokens: &Vec<Token>, position: &mut usize) -> Result<Node, ParseError> {
-    let inner = atom(tokens, position)?;
+fn starts_term(kind: &TokenKind) -> bool {
+    if let TokenKind::Word(word) = kind {
+        return !is_keyword(word);
+    }
+
+    matches!(
+        kind,
+        TokenKind::Integer(_)
+            | TokenKind::Float(_)
+            | TokenKind::Symbol('(')
+            | TokenKind::Symbol('[')
+            | TokenKind::Symbol('√')
+    )
+}
+
+// Adjacent terms such as `2 pi r` or `3 [m] 2` are multiplied
+fn implicit_product(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    let mut result = term(tokens, position)?;
+
+    loop {
+        let next_token = curr_token(tokens, position);
+        if !starts_term(&next_token.kind) {
+            return Ok(result);
+        }
+
+        let rhs = term(tokens, position)?;
+        result = Node {
+            content: NodeContent::Binary(Box::new(result), BinaryOp::Mul, Box::new(rhs)),
+            start: next_token.start,
+            end: next_token.end,
+        };
+    }
+}
+
+pub fn term(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    let mut result = unit_term(tokens, position)?;
+    if !matches!(result.content, NodeContent::Unary(UnaryOp::Units(_), _)) {
+        return Ok(result);
+    }
+
+    // Juxtaposed quantities with units such as 5 [ft] 3 [in] or 12° 30′ are summed
+    loop {
+        let is_number = matches!(
+            curr_token(tokens, position).kind,
+            TokenKind::Integer(_) | TokenKind::Float(_)
+        );
+        if !is_number {
+            return Ok(result);
+        }
+        let next_token = &tokens[*position + 1];
+        if next_token.kind != TokenKind::Symbol('[') && postfix_unit(next_token).is_none() {
+            return Ok(result);
+        }
+
+        let rhs = unit_term(tokens, position)?;
+        let (start, end) = (rhs.start, rhs.end);
+        result = Node {
+            content: NodeContent::Binary(Box::new(result), BinaryOp::Add, Box::new(rhs)),
+            start,
+            end,
+        };
+    }
+}
+
+fn unit_term(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    // A bracket without a number before it is one of the unit, like in [kg] * 9.81
+    let first_token = curr_token(tokens, position);
+    let inner = if first_token.kind == TokenKind::Symbol('[') {
+        Node {
+            content: NodeContent::Value(Rational::ONE.into()),
+            start: first_token.start,
+            end: first_token.start,
+        }
+    } else {
+        power(tokens, position)?
+    };
 
     let first_suffix_token = curr_token(tokens, position);
-    match first_suffix_token.kind {
-        TokenKind::Symbol('[') => {
-            let unit_terms = super::units::parse_units(tokens, position)?;
-            Ok(Node {
-                content: NodeContent::Unary(
-                    crate::eval::UnaryOp::Units(unit_terms),
-                    Box::new(inner),
-                ),
-                start: first_suffix_token.start,
-                end: first_suffix_token.end,
-            })
+    let unit_terms = match first_suffix_token.kind {
+        TokenKind::Symbol('[') => parse_units(tokens, position)?,
+        _ => match postfix_unit(first_suffix_token) {
+            Some(unit_terms) => {
+                step_token(tokens, position);
+                unit_terms
+            }
+            None => return Ok(inner),
+        },
+    };
+
+    Ok(Node {
+        content: NodeContent::Unary(UnaryOp::Units(unit_terms), Box::new(inner)),
+        start: first_suffix_token.start,
+        end: first_suffix_token.end,
+    })
+}
+
+// A '√' or '-' before or superscript digits after an atom, so `√2` and `r²` need no
+// parentheses. A minus sign takes the power, `-x²` is -(x²), but not units after it, so
+// `-30 [dBm]` is a level of -30 dBm.
+fn power(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    let prefix_token = curr_token(tokens, position);
+    let mut result = match prefix_token.kind {
+        TokenKind::Symbol('√') => {
+            step_token(tokens, position);
+            let radicand = power(tokens, position)?;
+            exponentiate(radicand, Rational::new(1, 2), prefix_token)
+        }
+        TokenKind::Symbol('-') => {
+            step_token(tokens, position);
+            let operand = power(tokens, position)?;
+            Node {
+                content: NodeContent::Unary(UnaryOp::Negative, Box::new(operand)),
+                start: prefix_token.start,
+                end: prefix_token.end,
+            }
+        }
+        _ => atom(tokens, position)?,
+    };
+
+    loop {
+        let exponent_token = curr_token(tokens, position);
+        match exponent_token.kind {
+            TokenKind::Superscript(n) => {
+                step_token(tokens, position);
+                result = exponentiate(result, Rational::new(n, 1), exponent_token);
+            }
+            _ => return Ok(result),
         }
-        _ => Ok(inner),
     }
 }
 
-pub fn atom(tokens: &Vec<Token>, position: &mut usize) -> Result<Node, ParseError> {
+fn exponentiate(base: Node, exponent: Rational, op_token: &Token) -> Node {
+    let exponent = Node {
+        content: NodeContent::Value(exponent.into()),
+        start: op_token.start,
+        end: op_token.end,
+    };
+
+    Node {
+        content: NodeContent::Binary(Box::new(base), BinaryOp::Pow, Box::new(exponent)),
+        start: op_token.start,
+        end: op_token.end,
+    }
+}
+
+pub fn atom(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
     let curr = curr_token(tokens, position);
     step_token(tokens, position);
 
@@ -86,11 +300,52 @@ pub fn atom(tokens: &Vec<Token>, position: &mut usize) -> Result<Node, ParseErro
             start: curr.start,
             end: curr.end,
         }),
-        TokenKind::Word(s) => Ok(Node {
-            content: NodeContent::Variable(s.clone()),
+        &TokenKind::Float(x) => Ok(Node {
+            content: NodeContent::Value(Quantity::from_float(x).into()),
             start: curr.start,
             end: curr.end,
         }),
+        TokenKind::Word(s) => {
+            // Only a parenthesis right after the name makes a call, `f (x)` is f times x
+            let is_call = curr_token(tokens, position).start == curr.end;
+            if !is_call || optional(TokenKind::Symbol('('), tokens, position).is_none() {
+                return Ok(Node {
+                    content: NodeContent::Variable(s.clone()),
+                    start: curr.start,
+                    end: curr.end,
+                });
+            }
+
+            let mut params = call_params(tokens, position)?;
+            let (_, end) = expect(TokenKind::Symbol(')'), tokens, position)?;
+            if s != "if" {
+                return Ok(Node {
+                    content: NodeContent::Function(s.clone(), params),
+                    start: curr.start,
+                    end,
+                });
+            }
+
+            if params.len() != 3 {
+                return Err(ParseError {
+                    reason: "expected a condition and two values".into(),
+                    start: curr.start,
+                    end,
+                });
+            }
+            let if_false = params.pop().unwrap();
+            let if_true = params.pop().unwrap();
+            let condition = params.pop().unwrap();
+            Ok(Node {
+                content: NodeContent::Conditional(
+                    Box::new(condition),
+                    Box::new(if_true),
+                    Box::new(if_false),
+                ),
+                start: curr.start,
+                end,
+            })
+        }
         TokenKind::Symbol('(') => {
             let inner = expr(tokens, position);
             expect(TokenKind::Symbol(')'), tokens, position)?;
@@ -103,3 +358,17 @@ pub fn atom(tokens: &Vec<Token>, position: &mut usize) -> Result<Node, ParseErro
         }),
     }
 }
+
+fn call_params(tokens: &[Token], position: &mut usize) -> Result<Vec<Node>, ParseError> {
+    let mut params = Vec::new();
+    if curr_token(tokens, position).kind == TokenKind::Symbol(')') {
+        return Ok(params);
+    }
+
+    loop {
+        params.push(expr(tokens, position)?);
+        if optional(TokenKind::Symbol(','), tokens, position).is_none() {
+            return Ok(params);
+        }
+    }
+}
@@ -1,6 +1,56 @@
 use super::{ParseError, Token, TokenKind};
 use std::{iter::Peekable, str::Chars};
 
+const COMPOUND_SYMBOLS: [&str; 6] = ["->", "<=", ">=", "==", "!=", "~="];
+
+// Operators pasted from papers and spec sheets are read as their ASCII counterparts
+fn ascii_symbol(c: char) -> char {
+    match c {
+        '×' | '·' | '⋅' | '∗' => '*',
+        '÷' | '∕' => '/',
+        '−' | '–' => '-',
+        _ => c,
+    }
+}
+
+fn unicode_compound(c: char) -> Option<&'static str> {
+    match c {
+        '→' => Some("->"),
+        '≤' => Some("<="),
+        '≥' => Some(">="),
+        '≠' => Some("!="),
+        _ => None,
+    }
+}
+
+// Letter-like symbols with a preferred equivalent, such as the ohm sign and capital omega
+fn canonical_letter(c: char) -> char {
+    match c {
+        '\u{2126}' => 'Ω',
+        '\u{212A}' => 'K',
+        '\u{212B}' => 'Å',
+        _ => c,
+    }
+}
+
+fn superscript_digit(c: char) -> Option<i32> {
+    let digit = match c {
+        '⁰' => 0,
+        '¹' => 1,
+        '²' => 2,
+        '³' => 3,
+        '⁴' => 4,
+        '⁵' => 5,
+        '⁶' => 6,
+        '⁷' => 7,
+        '⁸' => 8,
+        '⁹' => 9,
+        _ => return None,
+    };
+
+    Some(digit)
+}
+
 pub struct Lexer<'a> {
     pub chars: Peekable<Chars<'a>>,
     pub buffer: String,
@@ -18,39 +68,157 @@ impl<'a> Lexer<'a> {
             return Ok(self.create_token(TokenKind::End));
         }
 
-        if currchar.is_alphabetic() {
+        if superscript_digit(currchar).is_some() || matches!(currchar, '⁻' | '⁺') {
+            self.lex_superscript()
+        } else if currchar.is_alphabetic() {
             Ok(self.lex_word())
-        } else if currchar.is_numeric() || currchar == '.' {
-            Ok(self.lex_number())
-        } else if currchar == '<' {
+        } else if currchar.is_ascii_digit() || currchar == '.' {
+            self.lex_number()
+        } else if currchar == '<' && self.quoted_name_follows() {
             self.lex_string()
         } else {
+            Ok(self.lex_symbol())
+        }
+    }
+
+    fn lex_symbol(&mut self) -> Token {
+        let first = ascii_symbol(self.next_char());
+        if let Some(symbol) = unicode_compound(first) {
+            return self.create_token(TokenKind::Compound(symbol));
+        }
+        let second = self.peek_char();
+
+        for symbol in COMPOUND_SYMBOLS {
+            if symbol.chars().eq([first, second]) {
+                self.next_char();
+                return self.create_token(TokenKind::Compound(symbol));
+            }
+        }
+
+        self.create_token(TokenKind::Symbol(first))
+    }
+
+    fn lex_number(&mut self) -> Result<Token, ParseError> {
+        self.consume_while(|c| c.is_ascii_digit() || c == '_');
+
+        let is_integer = self.peek_char() != '.' && !self.exponent_follows();
+        if self.peek_char() == '.' {
             self.next_char();
-            Ok(self.create_token(TokenKind::Symbol(currchar)))
+            self.consume_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        if self.exponent_follows() {
+            self.next_char(); // e
+            if matches!(self.peek_char(), '+' | '-') {
+                self.next_char();
+            }
+            self.consume_while(|c| c.is_ascii_digit());
+        }
+
+        let clean_str = self
+            .get_buffer_str()
+            .chars()
+            .filter(|&c| c != '_')
+            .collect::<String>();
+
+        // Integers too large for u32 are still valid numbers as floats
+        if is_integer && let Ok(result) = clean_str.parse::<u32>() {
+            return Ok(self.create_token(TokenKind::Integer(result)));
+        }
+
+        match clean_str.parse::<f64>() {
+            Ok(result) => Ok(self.create_token(TokenKind::Float(result))),
+            Err(_) => Err(ParseError {
+                reason: "invalid number".into(),
+                start: self.token_start,
+                end: self.strpos,
+            }),
+        }
+    }
+
+    // An 'e' only starts an exponent when followed by digits, so `2e` is still 2 times e
+    fn exponent_follows(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        if !matches!(ahead.next(), Some('e' | 'E')) {
+            return false;
+        }
+
+        match ahead.next() {
+            Some('+' | '-') => ahead.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
         }
     }
 
-    fn lex_number(&mut self) -> Token {
-        self.consume_while(|c| c.is_numeric() || c == '_');
+    // A '<' starts a quoted name like `<speed of light>` only when a '>' closes a name of
+    // words and what follows cannot be the other side of a comparison, so `x < 2`, `x<y`,
+    // `a<b and b>c` and `if(a<b, 1, 2)` still compare
+    fn quoted_name_follows(&self) -> bool {
+        let mut ahead = self.chars.clone();
+        ahead.next(); // <
 
-        // Buffer will always be populated by at least one numeric char here
-        // because of the initial requirement of char::is_numeric for this function to be called
-        if self.peek_char() != '.' {
-            let clean_str = self
-                .get_buffer_str()
-                .chars()
-                .filter(|&c| c != '_')
-                .collect::<String>();
-            let result = clean_str.parse::<u32>().unwrap();
-            return self.create_token(TokenKind::Integer(result));
+        let mut name = String::new();
+        loop {
+            match ahead.next() {
+                Some('>') => break,
+                Some(c) if c.is_alphanumeric() || c == '_' || c == ' ' => name.push(c),
+                _ => return false,
+            }
+        }
+        if !name.starts_with(char::is_alphabetic)
+            || name
+                .split(' ')
+                .any(|word| matches!(word, "and" | "or" | "not"))
+        {
+            return false;
         }
 
-        todo!()
+        // `<b>= c` and `<b> c` read as comparisons with `b`
+        if ahead.clone().next() == Some('=') {
+            return false;
+        }
+        match ahead.find(|c| !c.is_whitespace()) {
+            Some(c) => !(c.is_alphanumeric() && superscript_digit(c).is_none() || "(.".contains(c)),
+            None => true,
+        }
     }
 
     fn lex_word(&mut self) -> Token {
-        self.consume_while(|c| c.is_alphanumeric() || c == '_');
-        self.create_token(TokenKind::Word(self.get_buffer_str()))
+        self.consume_while(|c| (c.is_alphanumeric() || c == '_') && superscript_digit(c).is_none());
+        let word = self.buffer.chars().map(canonical_letter).collect();
+        self.create_token(TokenKind::Word(word))
+    }
+
+    fn lex_superscript(&mut self) -> Result<Token, ParseError> {
+        let negative = match self.peek_char() {
+            '⁻' => {
+                self.next_char();
+                true
+            }
+            '⁺' => {
+                self.next_char();
+                false
+            }
+            _ => false,
+        };
+
+        let mut value = 0i32;
+        let mut has_digits = false;
+        while let Some(digit) = superscript_digit(self.peek_char()) {
+            self.next_char();
+            has_digits = true;
+            value = value.saturating_mul(10).saturating_add(digit);
+        }
+
+        if !has_digits {
+            return Err(ParseError {
+                reason: "expected superscript digits".into(),
+                start: self.token_start,
+                end: self.strpos,
+            });
+        }
+
+        let value = if negative { -value } else { value };
+        Ok(self.create_token(TokenKind::Superscript(value)))
     }
 
     fn lex_string(&mut self) -> Result<Token, ParseError> {
@@ -1,7 +1,11 @@
-use crate::eval::Node;
+use crate::eval::{Node, Statement};
 
 mod expr;
 mod lex;
+mod statement;
+#[cfg(test)]
+mod tests;
+pub mod unitfile;
 pub mod units;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -10,6 +14,9 @@ pub enum TokenKind {
     Float(f64),
     Word(String),
     Symbol(char),
+    Compound(&'static str),
+    /// A run of superscript digits with an optional sign, like `²` or `⁻¹`
+    Superscript(i32),
     End,
 }
 
@@ -27,6 +34,14 @@ pub struct ParseError {
     pub end: usize,
 }
 
+/// A `ParseError` within a line of a unit definition file, lines are counted from 1.
+#[derive(Debug, Clone)]
+pub struct UnitFileError {
+    pub file: String,
+    pub line: usize,
+    pub error: ParseError,
+}
+
 pub fn parse(tokens: Vec<Token>) -> Result<Node, ParseError> {
     let mut position = 0;
     let result = expr::expr(&tokens, &mut position)?;
@@ -43,6 +58,32 @@ pub fn parse(tokens: Vec<Token>) -> Result<Node, ParseError> {
     }
 }
 
+pub fn parse_statement(tokens: Vec<Token>) -> Result<Statement, ParseError> {
+    let mut position = 0;
+    let result = statement::statement(&tokens, &mut position)?;
+
+    let final_token = &tokens[position];
+
+    match final_token.kind {
+        TokenKind::End => Ok(result),
+        _ => Err(ParseError {
+            reason: "not end".into(),
+            start: final_token.start,
+            end: final_token.end,
+        }),
+    }
+}
+
+/// Lexes and parses one line of input. A function defined by the line keeps it as the
+/// text that the spans of its errors refer to.
+pub fn parse_line(s: &str) -> Result<Statement, ParseError> {
+    let mut statement = lex(s).and_then(parse_statement)?;
+    if let Statement::FunctionDefinition { evaluator, .. } = &mut statement {
+        evaluator.evalstr = s.into()
+    }
+    Ok(statement)
+}
+
 pub fn lex(s: &str) -> Result<Vec<Token>, ParseError> {
     let mut l = lex::Lexer {
         chars: s.chars().peekable(),
@@ -64,11 +105,11 @@ pub fn lex(s: &str) -> Result<Vec<Token>, ParseError> {
     Ok(result)
 }
 
-pub fn curr_token<'a>(tokens: &'a Vec<Token>, position: &mut usize) -> &'a Token {
+pub fn curr_token<'a>(tokens: &'a [Token], position: &mut usize) -> &'a Token {
     &tokens[*position]
 }
 
-pub fn step_token(tokens: &Vec<Token>, position: &mut usize) {
+pub fn step_token(tokens: &[Token], position: &mut usize) {
     if *position < tokens.len() - 1 {
         *position += 1
     }
@@ -76,7 +117,7 @@ pub fn step_token(tokens: &Vec<Token>, position: &mut usize) {
 
 pub fn expect(
     kind: TokenKind,
-    tokens: &Vec<Token>,
+    tokens: &[Token],
     position: &mut usize,
 ) -> Result<(usize, usize), ParseError> {
     let curr = curr_token(tokens, position);
@@ -92,11 +133,7 @@ pub fn expect(
     Ok((curr.start, curr.end))
 }
 
-pub fn optional(
-    kind: TokenKind,
-    tokens: &Vec<Token>,
-    position: &mut usize,
-) -> Option<(usize, usize)> {
+pub fn optional(kind: TokenKind, tokens: &[Token], position: &mut usize) -> Option<(usize, usize)> {
     let curr = curr_token(tokens, position);
     if curr.kind != kind {
         None
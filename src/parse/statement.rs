@@ -0,0 +1,155 @@
+use super::{
+    ParseError, Token, TokenKind, curr_token, expect, expr::expr, optional, step_token,
+    units::parse_units,
+};
+use crate::eval::{DimensionAnnotation, Evaluator, Statement};
+
+pub fn statement(tokens: &[Token], position: &mut usize) -> Result<Statement, ParseError> {
+    let is_unit_declaration = matches!(
+        (&tokens[*position].kind, tokens.get(*position + 1).map(|t| &t.kind)),
+        (TokenKind::Word(keyword), Some(TokenKind::Word(_))) if keyword == "unit"
+    );
+
+    let is_const_definition = matches!(
+        (
+            &tokens[*position].kind,
+            tokens.get(*position + 1).map(|t| &t.kind)
+        ),
+        (TokenKind::Word(_), Some(TokenKind::Symbol('=')))
+    );
+
+    // A call is only ever a definition, as expressions have no '='
+    let is_function_definition = matches!(
+        (
+            &tokens[*position].kind,
+            tokens.get(*position + 1).map(|t| &t.kind)
+        ),
+        (TokenKind::Word(_), Some(TokenKind::Symbol('(')))
+    ) && tokens[*position..]
+        .iter()
+        .any(|t| t.kind == TokenKind::Symbol('='));
+
+    if is_unit_declaration {
+        unit_declaration(tokens, position)
+    } else if is_const_definition {
+        const_definition(tokens, position)
+    } else if is_function_definition {
+        function_definition(tokens, position)
+    } else {
+        Ok(Statement::Expression(expr(tokens, position)?))
+    }
+}
+
+// unit <name> = <expr>
+fn unit_declaration(tokens: &[Token], position: &mut usize) -> Result<Statement, ParseError> {
+    step_token(tokens, position); // unit
+
+    let name_token = curr_token(tokens, position);
+    let name = match &name_token.kind {
+        TokenKind::Word(name) => name.clone(),
+        _ => unreachable!(),
+    };
+    step_token(tokens, position);
+    expect(TokenKind::Symbol('='), tokens, position)?;
+
+    let value = expr(tokens, position)?;
+
+    Ok(Statement::UnitDeclaration {
+        name,
+        value,
+        start: name_token.start,
+        end: name_token.end,
+    })
+}
+
+// <name> = <expr>
+fn const_definition(tokens: &[Token], position: &mut usize) -> Result<Statement, ParseError> {
+    let name_token = curr_token(tokens, position);
+    let name = match &name_token.kind {
+        TokenKind::Word(name) => name.clone(),
+        _ => unreachable!(),
+    };
+    step_token(tokens, position);
+    step_token(tokens, position); // =
+
+    let value = expr(tokens, position)?;
+
+    Ok(Statement::ConstDefinition {
+        name,
+        value,
+        start: name_token.start,
+        end: name_token.end,
+    })
+}
+
+// <name>(<param> [<units>], ...) [<units>] = <expr>, where the units are optional
+fn function_definition(tokens: &[Token], position: &mut usize) -> Result<Statement, ParseError> {
+    let name_token = curr_token(tokens, position);
+    let name = match &name_token.kind {
+        TokenKind::Word(name) => name.clone(),
+        _ => unreachable!(),
+    };
+    step_token(tokens, position);
+    step_token(tokens, position); // (
+
+    let mut params = Vec::new();
+    let mut param_dims = Vec::new();
+    if optional(TokenKind::Symbol(')'), tokens, position).is_none() {
+        loop {
+            let param_token = curr_token(tokens, position);
+            match &param_token.kind {
+                TokenKind::Word(param) => params.push(param.clone()),
+                _ => {
+                    return Err(ParseError {
+                        reason: "expected a parameter name".into(),
+                        start: param_token.start,
+                        end: param_token.end,
+                    });
+                }
+            }
+            step_token(tokens, position);
+            param_dims.push(dimension_annotation(tokens, position)?);
+
+            if optional(TokenKind::Symbol(','), tokens, position).is_none() {
+                expect(TokenKind::Symbol(')'), tokens, position)?;
+                break;
+            }
+        }
+    }
+
+    let result_dim = dimension_annotation(tokens, position)?;
+    expect(TokenKind::Symbol('='), tokens, position)?;
+    let body = expr(tokens, position)?;
+
+    Ok(Statement::FunctionDefinition {
+        name,
+        evaluator: Evaluator {
+            parent: body,
+            // Filled in by whoever has the source of the line
+            evalstr: String::new(),
+            params,
+            param_dims,
+            result_dim,
+            signature: Default::default(),
+        },
+        start: name_token.start,
+        end: name_token.end,
+    })
+}
+
+fn dimension_annotation(
+    tokens: &[Token],
+    position: &mut usize,
+) -> Result<Option<DimensionAnnotation>, ParseError> {
+    let open_token = curr_token(tokens, position);
+    if open_token.kind != TokenKind::Symbol('[') {
+        return Ok(None);
+    }
+
+    let units = parse_units(tokens, position)?;
+    Ok(Some(DimensionAnnotation {
+        units,
+        start: open_token.start,
+        end: tokens[*position - 1].end,
+    }))
+}
@@ -0,0 +1,181 @@
+use super::{ParseError, lex, parse, units::parse_units};
+use crate::{eval::UnitExpr, rational::Rational};
+
+fn repr(s: &str) -> String {
+    let tokens = lex(s).unwrap_or_else(|e| panic!("`{s}` fails to lex: {e:?}"));
+    match parse(tokens) {
+        Ok(node) => node.repr(),
+        Err(e) => panic!("`{s}` fails to parse: {e:?}"),
+    }
+}
+
+fn unit_expr(s: &str) -> Result<UnitExpr, ParseError> {
+    let tokens = lex(s).unwrap_or_else(|e| panic!("`{s}` fails to lex: {e:?}"));
+    parse_units(&tokens, &mut 0)
+}
+
+// The factor and the text, power and span of each term, where spans count characters
+fn units(s: &str) -> (f64, Vec<(&str, Rational, usize, usize)>) {
+    let units = unit_expr(s).unwrap_or_else(|e| panic!("`{s}` fails to parse: {e:?}"));
+    let byte = |i: usize| s.char_indices().nth(i).map_or(s.len(), |(byte, _)| byte);
+    let terms = units
+        .terms
+        .iter()
+        .map(|term| {
+            let text = &s[byte(term.start)..byte(term.end)];
+            (text, term.power, term.start, term.end)
+        })
+        .collect();
+    (units.factor, terms)
+}
+
+fn powers(s: &str) -> Vec<(&str, Rational)> {
+    units(s)
+        .1
+        .into_iter()
+        .map(|(unit, power, ..)| (unit, power))
+        .collect()
+}
+
+fn unit_error(s: &str) -> String {
+    match unit_expr(s) {
+        Ok(units) => panic!("`{s}` parses as {units:?}"),
+        Err(e) => e.reason,
+    }
+}
+
+#[test]
+fn prefix_minus() {
+    assert_eq!(repr("-3"), "-3");
+    assert_eq!(repr("−3"), "-3");
+    assert_eq!(repr("2 * -3"), "(2 * -3)");
+    assert_eq!(repr("2 - -3"), "(2 - -3)");
+    assert_eq!(repr("sin(-1)"), "sin(-1)");
+    assert_eq!(repr("-x²"), "-(x²)");
+    assert_eq!(repr("-2 x"), "(-2 * x)");
+    assert_eq!(repr("-a / b"), "(-a / b)");
+    assert_eq!(repr("-30 [dBm]"), "(-30 [dBm])");
+    assert_eq!(repr("1 -2"), "(1 - 2)");
+}
+
+#[test]
+fn implicit_multiplication() {
+    assert_eq!(repr("f(x)"), "f(x)");
+    assert_eq!(repr("f (x)"), "(f * x)");
+    assert_eq!(repr("2 pi r"), "((2 * pi) * r)");
+    assert_eq!(repr("1/2 pi"), "(1 / (2 * pi))");
+    assert_eq!(repr("a:2b"), "(a / (2 * b))");
+    assert_eq!(repr("a:2*b"), "((a / 2) * b)");
+    assert_eq!(repr("2 x²"), "(2 * (x²))");
+    assert_eq!(repr("2x²"), "(2 * (x²))");
+}
+
+#[test]
+fn comparisons_and_quoted_names() {
+    assert_eq!(repr("x<y"), "(x < y)");
+    assert_eq!(repr("x < 2"), "(x < 2)");
+    assert_eq!(repr("a<b and b>c"), "((a < b) and (b > c))");
+    assert_eq!(repr("3<x and x>1"), "((3 < x) and (x > 1))");
+    assert_eq!(repr("if(a<b, 1, 2)"), "if((a < b), 1, 2)");
+    assert_eq!(repr("a<b>=c"), "((a < b) >= c)");
+    assert_eq!(repr("a<b> c"), "((a < b) > c)");
+    assert_eq!(repr("<speed of light> * 2"), "(speed of light * 2)");
+    assert_eq!(repr("2 <speed of light>"), "(2 * speed of light)");
+    assert_eq!(repr("<c>²"), "(c²)");
+}
+
+#[test]
+fn powers_parse_again() {
+    for s in [
+        "x²",
+        "x⁻¹",
+        "√x",
+        "√(x³)",
+        "(√x)³",
+        "√√x",
+        "2 [m² s⁻¹]",
+        "x [m^(1/2)]",
+    ] {
+        let once = repr(s);
+        assert_eq!(repr(&once), once, "`{s}` is written as `{once}`");
+        assert!(!once.contains(" ^ "), "`{s}` is written as `{once}`");
+    }
+    assert_eq!(repr("x⁻¹²"), "(x⁻¹²)");
+    assert_eq!(repr("√x"), "(√x)");
+    assert_eq!(repr("2 [m² s⁻¹]"), "(2 [m² s⁻¹])");
+}
+
+#[test]
+fn unit_brackets() {
+    let r = |n: i32, d: u32| Rational::new(n, d);
+
+    assert_eq!(
+        units("[J/(kg K)]"),
+        (
+            1.,
+            vec![
+                ("J", r(1, 1), 1, 2),
+                ("kg", r(-1, 1), 4, 6),
+                ("K", r(-1, 1), 7, 8)
+            ]
+        )
+    );
+    assert_eq!(powers("[J/kg/K]"), powers("[J/(kg K)]"));
+    assert_eq!(powers("[J/kg K]"), powers("[J/(kg K)]"));
+    assert_eq!(
+        units("[kg*m·s]").1,
+        vec![
+            ("kg", r(1, 1), 1, 3),
+            ("m", r(1, 1), 4, 5),
+            ("s", r(1, 1), 6, 7)
+        ]
+    );
+    assert_eq!(units("[m^0.5]").1, vec![("m", r(1, 2), 1, 2)]);
+    assert_eq!(units("[m^-1/2]").1, vec![("m", r(-1, 2), 1, 2)]);
+    assert_eq!(units("[m^(3/2)]").1, vec![("m", r(3, 2), 1, 2)]);
+    assert_eq!(powers("[(m/s)^2]"), powers("[m^2 s^-2]"));
+    assert_eq!(units("[1e3 kg]"), (1e3, vec![("kg", r(1, 1), 5, 7)]));
+    assert_eq!(units("[1/s]"), (1., vec![("s", r(-1, 1), 3, 4)]));
+    assert_eq!(units("[2^3]").0, 8.);
+    assert_eq!(
+        units("[m²/s²]").1,
+        vec![("m", r(2, 1), 1, 2), ("s", r(-2, 1), 4, 5)]
+    );
+    assert_eq!(units("[]"), (1., vec![]));
+}
+
+#[test]
+fn unit_exponent_errors() {
+    for s in ["[m^0/0]", "[m^1/0]", "[m^(1/0)]"] {
+        assert_eq!(unit_error(s), "exponent has a denominator of zero", "{s}");
+    }
+    for s in [
+        "[(m^50000)^50000]",
+        "[m^3000000000]",
+        "[(m^(1/100000))^(1/100000)]",
+    ] {
+        assert_eq!(unit_error(s), "exponent is too large", "{s}");
+    }
+    assert_eq!(unit_error("[m^x]"), "expected exponent");
+    assert_eq!(
+        unit_error("[m^0.1234567]"),
+        "exponent is not a short decimal"
+    );
+    assert_eq!(unit_error("[(m s]"), "unexpected");
+}
+
+#[test]
+fn mixed_unit_conversions() {
+    let mixed = "(t -> [h] [min] [s])";
+    assert_eq!(repr("t -> [h:min:s]"), mixed);
+    assert_eq!(repr("t -> [h] [min] [s]"), mixed);
+    assert_eq!(repr("t -> [h:min] [s]"), mixed);
+    assert_eq!(repr("t -> [km/h : m/s]"), "(t -> [km h⁻¹] [m s⁻¹])");
+    assert_eq!(repr("t -> [h]"), "(t -> [h])");
+
+    let parse_error = |s: &str| parse(lex(s).unwrap()).unwrap_err().reason;
+    for s in ["t -> [h:]", "t -> [:h]", "t -> [h::s]", "2 [h:min]"] {
+        assert_eq!(parse_error(s), "expected unit", "{s}");
+    }
+    assert_eq!(repr("t -> []"), "(t -> [])");
+}
@@ -0,0 +1,208 @@
+use super::{
+    ParseError, Token, TokenKind, UnitFileError, curr_token, expect, lex, optional, step_token,
+    units::parse_unit_expr,
+};
+use crate::eval::{UnitExpr, UnitTerm};
+
+#[derive(Debug, Clone)]
+pub enum DefinitionKind {
+    /// `dimension bit`, a base dimension along with a unit of the same name. The dimension
+    /// is only registered when the name is new, so `dimension s` gives the unit of time.
+    Dimension,
+    /// `prefix kibi- = 1024`
+    Prefix(f64),
+    /// `alias kibi- = Ki-`
+    PrefixAlias(String),
+    /// `furlong = 201.168 m`, aliases of units are lowered to a single term
+    Unit(UnitExpr),
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Parses a unit definition file with one definition per line, `#` starts a comment.
+///
+/// ```text
+/// dimension bit
+/// prefix kibi- = 2^10
+/// alias metre = meter
+/// furlong = 201.168 m
+/// hertz = 1/s
+/// ```
+pub fn parse_definitions(file: &str, source: &str) -> Result<Vec<Definition>, UnitFileError> {
+    let mut definitions = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let at_line = |error| UnitFileError {
+            file: file.into(),
+            line: index + 1,
+            error,
+        };
+
+        let content = match line.split_once('#') {
+            Some((content, _)) => content,
+            None => line,
+        };
+
+        let tokens = lex(content).map_err(at_line)?;
+        if tokens[0].kind == TokenKind::End {
+            continue;
+        }
+
+        let mut position = 0;
+        let (name, kind, start, end) = definition(&tokens, &mut position).map_err(at_line)?;
+        expect(TokenKind::End, &tokens, &mut position).map_err(at_line)?;
+
+        definitions.push(Definition {
+            name,
+            kind,
+            line: index + 1,
+            start,
+            end,
+        });
+    }
+
+    Ok(definitions)
+}
+
+type DefinitionHead = (String, DefinitionKind, usize, usize);
+
+fn definition(tokens: &[Token], position: &mut usize) -> Result<DefinitionHead, ParseError> {
+    let first = curr_token(tokens, position);
+
+    // Keywords are only keywords when a name follows, so a unit may still be called `prefix`
+    let keyword = match (&first.kind, &tokens[*position + 1].kind) {
+        (TokenKind::Word(keyword), TokenKind::Word(_)) => keyword.as_str(),
+        _ => "",
+    };
+
+    match keyword {
+        "dimension" => {
+            step_token(tokens, position);
+            let (name, start, end) = word(tokens, position)?;
+            Ok((name, DefinitionKind::Dimension, start, end))
+        }
+        "prefix" => {
+            step_token(tokens, position);
+            let (name, start, end) = prefix_name(tokens, position)?;
+            expect(TokenKind::Symbol('='), tokens, position)?;
+            let factor = number(tokens, position)?;
+            Ok((name, DefinitionKind::Prefix(factor), start, end))
+        }
+        "alias" => {
+            step_token(tokens, position);
+            if tokens[*position + 1].kind == TokenKind::Symbol('-') {
+                let (name, start, end) = prefix_name(tokens, position)?;
+                expect(TokenKind::Symbol('='), tokens, position)?;
+                let (target, _, _) = prefix_name(tokens, position)?;
+                return Ok((name, DefinitionKind::PrefixAlias(target), start, end));
+            }
+
+            let (name, start, end) = word(tokens, position)?;
+            expect(TokenKind::Symbol('='), tokens, position)?;
+            let (target, target_start, target_end) = word(tokens, position)?;
+            let units = UnitExpr {
+                factor: 1.,
+                terms: vec![UnitTerm {
+                    unit: target,
+                    start: target_start,
+                    end: target_end,
+                    power: 1.into(),
+                }],
+            };
+            Ok((name, DefinitionKind::Unit(units), start, end))
+        }
+        _ => {
+            let (name, start, end) = word(tokens, position)?;
+            expect(TokenKind::Symbol('='), tokens, position)?;
+            // A leading fraction like 1/3600 is a number rather than 1 per 3600 units
+            let factor = match curr_token(tokens, position).kind {
+                TokenKind::Integer(_) | TokenKind::Float(_) => number(tokens, position)?,
+                _ => 1.,
+            };
+            let units = UnitExpr::number(factor) * parse_unit_expr(tokens, position)?;
+            Ok((name, DefinitionKind::Unit(units), start, end))
+        }
+    }
+}
+
+fn word(tokens: &[Token], position: &mut usize) -> Result<(String, usize, usize), ParseError> {
+    let curr = curr_token(tokens, position);
+    match &curr.kind {
+        TokenKind::Word(s) => {
+            step_token(tokens, position);
+            Ok((s.clone(), curr.start, curr.end))
+        }
+        _ => Err(ParseError {
+            reason: "expected name".into(),
+            start: curr.start,
+            end: curr.end,
+        }),
+    }
+}
+
+fn prefix_name(
+    tokens: &[Token],
+    position: &mut usize,
+) -> Result<(String, usize, usize), ParseError> {
+    let (name, start, _) = word(tokens, position)?;
+    let (_, end) = expect(TokenKind::Symbol('-'), tokens, position)?;
+    Ok((name, start, end))
+}
+
+fn literal(tokens: &[Token], position: &mut usize) -> Result<f64, ParseError> {
+    let curr = curr_token(tokens, position);
+    let value = match curr.kind {
+        TokenKind::Integer(n) => n as f64,
+        TokenKind::Float(x) => x,
+        _ => {
+            return Err(ParseError {
+                reason: "expected number".into(),
+                start: curr.start,
+                end: curr.end,
+            });
+        }
+    };
+
+    step_token(tokens, position);
+    Ok(value)
+}
+
+// A literal with an optional integer power and an optional literal denominator, such as
+// 2^10 or 1/3600. A '/' not followed by a number divides by the units after it instead.
+fn number(tokens: &[Token], position: &mut usize) -> Result<f64, ParseError> {
+    let mut value = literal(tokens, position)?;
+
+    if optional(TokenKind::Symbol('^'), tokens, position).is_some() {
+        let negative = optional(TokenKind::Symbol('-'), tokens, position).is_some();
+        let power_token = curr_token(tokens, position);
+        let power = match power_token.kind {
+            TokenKind::Integer(n) => n as i32,
+            _ => {
+                return Err(ParseError {
+                    reason: "expected integer power".into(),
+                    start: power_token.start,
+                    end: power_token.end,
+                });
+            }
+        };
+        step_token(tokens, position);
+        value = value.powi(if negative { -power } else { power });
+    }
+
+    let before_slash = *position;
+    if optional(TokenKind::Symbol('/'), tokens, position).is_some() {
+        match curr_token(tokens, position).kind {
+            TokenKind::Integer(_) | TokenKind::Float(_) => value /= literal(tokens, position)?,
+            _ => *position = before_slash,
+        }
+    }
+
+    Ok(value)
+}
@@ -1,20 +1,55 @@
-use super::{ParseError, Token, TokenKind, curr_token, optional, step_token};
-use crate::{eval::UnitTerm, rational::Rational};
+use super::{ParseError, Token, TokenKind, curr_token, expect, optional, step_token};
+use crate::{
+    eval::{UnitExpr, UnitTerm},
+    rational::Rational,
+};
 
-pub fn parse_term_exponent(
-    tokens: &Vec<Token>,
-    position: &mut usize,
-) -> Result<Rational, ParseError> {
-    if optional(TokenKind::Symbol('^'), tokens, position).is_none() {
-        return Ok(Rational::new(1, 1));
+/// Angle symbols written straight after a number, as in `12° 30′`, without a bracket.
+pub const POSTFIX_UNITS: [char; 3] = ['°', '′', '″'];
+
+/// The unit of a postfix unit symbol token such as `°`.
+pub fn postfix_unit(token: &Token) -> Option<UnitExpr> {
+    match token.kind {
+        TokenKind::Symbol(c) if POSTFIX_UNITS.contains(&c) => Some(single_unit(c.into(), token)),
+        _ => None,
     }
+}
 
+fn single_unit(unit: String, token: &Token) -> UnitExpr {
+    UnitExpr {
+        factor: 1.,
+        terms: vec![UnitTerm {
+            unit,
+            start: token.start,
+            end: token.end,
+            power: Rational::ONE,
+        }],
+    }
+}
+
+fn parse_integer_exponent(tokens: &[Token], position: &mut usize) -> Result<Rational, ParseError> {
     let negative = optional(TokenKind::Symbol('-'), tokens, position).is_some();
 
     let numerator_token = curr_token(tokens, position);
     step_token(tokens, position);
     let numerator = match numerator_token.kind {
-        TokenKind::Integer(n) => n,
+        TokenKind::Integer(n) => i32::try_from(n).map_err(|_| ParseError {
+            reason: "exponent is too large".into(),
+            start: numerator_token.start,
+            end: numerator_token.end,
+        })?,
+        TokenKind::Float(x) => {
+            let exponent = Rational::from_decimal(x).ok_or_else(|| ParseError {
+                reason: "exponent is not a short decimal".into(),
+                start: numerator_token.start,
+                end: numerator_token.end,
+            })?;
+            return Ok(if negative {
+                exponent.negative()
+            } else {
+                exponent
+            });
+        }
         _ => {
             return Err(ParseError {
                 reason: "expected exponent".into(),
@@ -23,72 +58,212 @@ pub fn parse_term_exponent(
             });
         }
     };
-    let numerator = if negative {
-        -(numerator as i32)
-    } else {
-        numerator as i32
-    };
+    let numerator = if negative { -numerator } else { numerator };
 
     // Following '/' not proceeded by integer is the denominator of unit
     // Thus backtrack to here otherwise
     let after_numerator_pos = *position;
     if optional(TokenKind::Symbol('/'), tokens, position).is_none() {
-        return Ok(Rational::new(numerator as i32, 1));
+        return Ok(Rational::new(numerator, 1));
     }
 
     let denom_token = curr_token(tokens, position);
     match denom_token.kind {
+        TokenKind::Integer(0) => Err(ParseError {
+            reason: "exponent has a denominator of zero".into(),
+            start: denom_token.start,
+            end: denom_token.end,
+        }),
         TokenKind::Integer(denom) => {
             step_token(tokens, position);
-            Ok(Rational::new(numerator as i32, denom))
+            Ok(Rational::new(numerator, denom))
         }
         _ => {
             *position = after_numerator_pos;
-            Ok(Rational::new(numerator as i32, 1))
+            Ok(Rational::new(numerator, 1))
         }
     }
 }
 
-pub fn parse_units(tokens: &Vec<Token>, position: &mut usize) -> Result<Vec<UnitTerm>, ParseError> {
+/// Parses an optional exponent such as `^2`, `^-1/2`, `^0.5`, `^(3/2)` or `²`.
+pub fn parse_term_exponent(tokens: &[Token], position: &mut usize) -> Result<Rational, ParseError> {
+    if let TokenKind::Superscript(n) = curr_token(tokens, position).kind {
+        step_token(tokens, position);
+        return Ok(n.into());
+    }
+
+    if optional(TokenKind::Symbol('^'), tokens, position).is_none() {
+        return Ok(Rational::ONE);
+    }
+
+    if optional(TokenKind::Symbol('('), tokens, position).is_none() {
+        return parse_integer_exponent(tokens, position);
+    }
+
+    let exponent = parse_integer_exponent(tokens, position)?;
+    expect(TokenKind::Symbol(')'), tokens, position)?;
+    Ok(exponent)
+}
+
+/// Parses a bracketed unit expression, starting at the opening bracket.
+pub fn parse_units(tokens: &[Token], position: &mut usize) -> Result<UnitExpr, ParseError> {
     step_token(tokens, position); // [
 
-    let mut result = Vec::new();
-    let mut in_denom = false;
+    let result = parse_unit_expr(tokens, position)?;
+    close_units(tokens, position)?;
+    Ok(result)
+}
+
+/// Parses bracketed units to convert to, starting at the opening bracket. Units separated
+/// by ':' as in `[h:min:s]` are the parts of a mixed unit.
+pub fn parse_unit_groups(
+    tokens: &[Token],
+    position: &mut usize,
+) -> Result<Vec<UnitExpr>, ParseError> {
+    step_token(tokens, position); // [
+
+    let mut groups = Vec::new();
+    loop {
+        let first = curr_token(tokens, position);
+        let units = parse_unit_expr(tokens, position)?;
+        let separated = optional(TokenKind::Symbol(':'), tokens, position).is_some();
+        // Only a lone `[]` may be empty
+        if (separated || !groups.is_empty()) && !starts_unit_factor(&first.kind) {
+            return Err(ParseError {
+                reason: "expected unit".into(),
+                start: first.start,
+                end: first.end,
+            });
+        }
+        groups.push(units);
+        if !separated {
+            break;
+        }
+    }
+    close_units(tokens, position)?;
+    Ok(groups)
+}
+
+fn close_units(tokens: &[Token], position: &mut usize) -> Result<(), ParseError> {
+    let closing_token = curr_token(tokens, position);
+    if closing_token.kind != TokenKind::Symbol(']') {
+        return Err(ParseError {
+            reason: "expected unit".into(),
+            start: closing_token.start,
+            end: closing_token.end,
+        });
+    }
+    step_token(tokens, position);
+    Ok(())
+}
+
+/// Parses units such as `J/(kg K)` or `1e3 kg·m^0.5`. Everything after a '/' is in the
+/// denominator, so `J/kg K` and `J/kg/K` are both the same as `J/(kg K)`.
+pub fn parse_unit_expr(tokens: &[Token], position: &mut usize) -> Result<UnitExpr, ParseError> {
+    let mut result = parse_unit_product(tokens, position)?;
+
+    while let Some((start, _)) = optional(TokenKind::Symbol('/'), tokens, position) {
+        let denominator = parse_unit_product(tokens, position)?;
+        result = result
+            * raise(
+                denominator,
+                Rational::ONE.negative(),
+                start,
+                tokens,
+                position,
+            )?;
+    }
+
+    Ok(result)
+}
+
+fn starts_unit_factor(kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::Word(_)
+        | TokenKind::Integer(_)
+        | TokenKind::Float(_)
+        | TokenKind::Symbol('(' | '\'') => true,
+        TokenKind::Symbol(c) => POSTFIX_UNITS.contains(c),
+        _ => false,
+    }
+}
+
+// Factors are multiplied by juxtaposition or an explicit '*' or '·', which is lexed as
+// '*'. An empty product is allowed so that `[/s]` and `[]` still work
+fn parse_unit_product(tokens: &[Token], position: &mut usize) -> Result<UnitExpr, ParseError> {
+    let mut result = UnitExpr::ONE;
 
     loop {
         let curr = curr_token(tokens, position);
-        step_token(tokens, position);
+        match curr.kind {
+            TokenKind::Symbol('*') => {
+                step_token(tokens, position);
+                result = result * parse_unit_factor(tokens, position)?;
+            }
+            ref kind if starts_unit_factor(kind) => {
+                result = result * parse_unit_factor(tokens, position)?;
+            }
+            _ => return Ok(result),
+        }
+    }
+}
 
-        let unit = match &curr.kind {
-            TokenKind::Word(s) => s.clone(),
-            TokenKind::Symbol(']') => return Ok(result),
-            TokenKind::Symbol('/') => {
-                if in_denom {
+fn parse_unit_factor(tokens: &[Token], position: &mut usize) -> Result<UnitExpr, ParseError> {
+    let curr = curr_token(tokens, position);
+    step_token(tokens, position);
+
+    let base = match &curr.kind {
+        TokenKind::Word(unit) => single_unit(unit.clone(), curr),
+        &TokenKind::Symbol(c) if POSTFIX_UNITS.contains(&c) => single_unit(c.into(), curr),
+        &TokenKind::Integer(n) => UnitExpr::number(n as f64),
+        &TokenKind::Float(x) => UnitExpr::number(x),
+        // A dimension variable of a function signature, such as `'D`
+        TokenKind::Symbol('\'') => {
+            let name_token = curr_token(tokens, position);
+            let name = match &name_token.kind {
+                TokenKind::Word(name) => name,
+                _ => {
                     return Err(ParseError {
-                        reason: "second usage of '/' in units not allowed".into(),
-                        start: curr.start,
-                        end: curr.end,
+                        reason: "expected the name of a dimension variable".into(),
+                        start: name_token.start,
+                        end: name_token.end,
                     });
                 }
-                in_denom = true;
-                continue;
-            }
-            _ => {
-                return Err(ParseError {
-                    reason: "expected unit".into(),
-                    start: curr.start,
-                    end: curr.end,
-                });
-            }
-        };
+            };
+            step_token(tokens, position);
+            let mut unit = single_unit(format!("'{name}"), name_token);
+            unit.terms[0].start = curr.start;
+            unit
+        }
+        TokenKind::Symbol('(') => {
+            let inner = parse_unit_expr(tokens, position)?;
+            expect(TokenKind::Symbol(')'), tokens, position)?;
+            inner
+        }
+        _ => {
+            return Err(ParseError {
+                reason: "expected unit".into(),
+                start: curr.start,
+                end: curr.end,
+            });
+        }
+    };
 
-        let exp = parse_term_exponent(tokens, position)?;
+    let exp = parse_term_exponent(tokens, position)?;
+    raise(base, exp, curr.start, tokens, position)
+}
 
-        result.push(UnitTerm {
-            unit,
-            power: if in_denom { exp.negative() } else { exp },
-            start: curr.start,
-            end: curr.end,
-        })
-    }
+// Nested powers such as `[(m^50000)^50000]` multiply their exponents, which may overflow
+fn raise(
+    units: UnitExpr,
+    power: Rational,
+    start: usize,
+    tokens: &[Token],
+    position: &usize,
+) -> Result<UnitExpr, ParseError> {
+    units.checked_pow(power).ok_or_else(|| ParseError {
+        reason: "exponent is too large".into(),
+        start,
+        end: tokens[*position - 1].end,
+    })
 }
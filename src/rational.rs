@@ -1,4 +1,5 @@
 use crate::utils::gcd;
+use std::ops::{Add, Div, Mul, Sub};
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Rational {
@@ -28,9 +29,9 @@ impl Default for Rational {
     }
 }
 
-impl Into<f64> for Rational {
-    fn into(self) -> f64 {
-        self.to_float()
+impl From<Rational> for f64 {
+    fn from(value: Rational) -> Self {
+        value.to_float()
     }
 }
 
@@ -60,6 +61,20 @@ impl Rational {
         }
     }
 
+    /// The exact fraction of a decimal with up to six places, such as 0.25 from `^0.25`.
+    pub fn from_decimal(x: f64) -> Option<Self> {
+        let mut denom = 1u32;
+        for _ in 0..=6 {
+            let scaled = x * denom as f64;
+            if (scaled - scaled.round()).abs() < 1e-9 && scaled.abs() <= i32::MAX as f64 {
+                return Some(Self::new(scaled.round() as i32, denom));
+            }
+            denom *= 10;
+        }
+
+        None
+    }
+
     pub fn is_zero(self) -> bool {
         self.numerator == 0
     }
@@ -90,7 +105,7 @@ impl Rational {
         let sign = self.numerator.signum();
         Self {
             numerator: sign * self.denominator as i32,
-            denominator: self.numerator.abs() as u32,
+            denominator: self.numerator.unsigned_abs(),
         }
     }
 
@@ -106,18 +121,6 @@ impl Rational {
         })
     }
 
-    pub fn add(self, other: Self) -> Self {
-        let common = gcd(self.denominator, other.denominator);
-
-        let left_factor = self.denominator / common;
-        let right_factor = other.denominator / common;
-
-        Self::new(
-            right_factor as i32 * self.numerator + left_factor as i32 * other.numerator,
-            left_factor * right_factor * common,
-        )
-    }
-
     pub fn checked_add(self, other: Self) -> Option<Self> {
         let common = gcd(self.denominator, other.denominator);
 
@@ -132,21 +135,10 @@ impl Rational {
         ))
     }
 
-    pub fn sub(self, other: Self) -> Self {
-        self.add(other.negative())
-    }
-
     pub fn checked_sub(self, other: Self) -> Option<Self> {
         self.checked_add(other.checked_negative()?)
     }
 
-    pub fn mul(self, other: Self) -> Self {
-        Self::new(
-            self.numerator * other.numerator,
-            self.denominator * other.denominator,
-        )
-    }
-
     pub fn checked_mul(self, other: Self) -> Option<Self> {
         Some(Self::new(
             self.numerator.checked_mul(other.numerator)?,
@@ -154,11 +146,50 @@ impl Rational {
         ))
     }
 
-    pub fn div(self, other: Self) -> Self {
-        self.mul(other.reciprocal())
-    }
-
     pub fn checked_div(self, other: Self) -> Option<Self> {
         self.checked_mul(other.checked_reciprocal()?)
     }
 }
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let common = gcd(self.denominator, other.denominator);
+
+        let left_factor = self.denominator / common;
+        let right_factor = other.denominator / common;
+
+        Self::new(
+            right_factor as i32 * self.numerator + left_factor as i32 * other.numerator,
+            left_factor * right_factor * common,
+        )
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Add::add(self, other.negative())
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.numerator * other.numerator,
+            self.denominator * other.denominator,
+        )
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        Mul::mul(self, other.reciprocal())
+    }
+}
@@ -1,12 +1,122 @@
-use super::{Complex, Quantity, Rational, SIDimension, ValueError};
+use super::{
+    Complex, ComplexDerivative, Derivatives, Quantity, Rational, SIDimension, ValueError,
+    ops::pow_cr,
+};
 use crate::f64plus::FloatPlus;
 
+fn constant(real: f64, imag: f64) -> Complex {
+    Complex {
+        real: FloatPlus::Scalar(real),
+        imag: FloatPlus::Scalar(imag),
+        derivatives: Derivatives::new(),
+        dim: SIDimension::DIMLESS,
+    }
+}
+
+// 1 - z with the imaginary part negated as it is in -z, so that -0 stays apart from 0.
+// Real arguments beyond a branch point then take the side of the cut that C99 and most
+// libraries do, as in asin(2) = pi/2 + 1.317i.
+fn one_minus(z: &Complex) -> Complex {
+    z.negative().unchecked_add(&constant(1., -0.))
+}
+
+fn mul_parts(
+    lr: &FloatPlus,
+    li: &FloatPlus,
+    rr: &FloatPlus,
+    ri: &FloatPlus,
+) -> (FloatPlus, FloatPlus) {
+    let operands = [lr, li, rr, ri];
+    (
+        FloatPlus::apply_fused_func(operands, |[lr, li, rr, ri]| lr * rr - li * ri),
+        FloatPlus::apply_fused_func(operands, |[lr, li, rr, ri]| li * rr + lr * ri),
+    )
+}
+
+fn div_parts(
+    lr: &FloatPlus,
+    li: &FloatPlus,
+    rr: &FloatPlus,
+    ri: &FloatPlus,
+) -> (FloatPlus, FloatPlus) {
+    let operands = [lr, li, rr, ri];
+    (
+        FloatPlus::apply_fused_func(operands, |[lr, li, rr, ri]| {
+            (lr * rr + li * ri) / (rr * rr + ri * ri)
+        }),
+        FloatPlus::apply_fused_func(operands, |[lr, li, rr, ri]| {
+            (li * rr - lr * ri) / (rr * rr + ri * ri)
+        }),
+    )
+}
+
+fn combine_derivatives<G>(lhs: &Complex, rhs: &Complex, dop: G) -> Derivatives<ComplexDerivative>
+where
+    G: Fn(&ComplexDerivative, &ComplexDerivative) -> ComplexDerivative,
+{
+    lhs.derivatives
+        .combine(&rhs.derivatives, &ComplexDerivative::ZERO, dop)
+}
+
+// For holomorphic f, d(f(z)) = f'(z) dz, so every derivative is scaled by the
+// same complex factor
+fn scale_derivatives(
+    c: &Complex,
+    factor_real: &FloatPlus,
+    factor_imag: &FloatPlus,
+) -> Derivatives<ComplexDerivative> {
+    c.derivatives
+        .map(|drv| drv.mul_complex(factor_real, factor_imag))
+}
+
+impl ComplexDerivative {
+    pub const ZERO: Self = ComplexDerivative {
+        real: FloatPlus::ZERO,
+        imag: FloatPlus::ZERO,
+    };
+
+    pub fn negative(&self) -> Self {
+        Self {
+            real: self.real.negative(),
+            imag: self.imag.negative(),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            real: self.real.add(&other.real),
+            imag: self.imag.add(&other.imag),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        Self {
+            real: self.real.sub(&other.real),
+            imag: self.imag.sub(&other.imag),
+        }
+    }
+
+    pub fn mul_complex(&self, real: &FloatPlus, imag: &FloatPlus) -> Self {
+        let (real, imag) = mul_parts(&self.real, &self.imag, real, imag);
+        Self { real, imag }
+    }
+
+    pub fn div_complex(&self, real: &FloatPlus, imag: &FloatPlus) -> Self {
+        let (real, imag) = div_parts(&self.real, &self.imag, real, imag);
+        Self { real, imag }
+    }
+}
+
 impl Complex {
     pub fn from_quantity(q: &Quantity) -> Self {
         Complex {
             real: q.value.clone(),
             imag: FloatPlus::Scalar(0.),
-            dim: q.dim,
+            derivatives: q.derivatives.map(|drv| ComplexDerivative {
+                real: drv.clone(),
+                imag: FloatPlus::ZERO,
+            }),
+            dim: q.dim.clone(),
         }
     }
 
@@ -14,6 +124,7 @@ impl Complex {
         Complex {
             real: FloatPlus::Scalar(r.to_float()),
             imag: FloatPlus::Scalar(0.),
+            derivatives: Derivatives::new(),
             dim: SIDimension::DIMLESS,
         }
     }
@@ -28,7 +139,8 @@ impl Complex {
         Self {
             real: self.real.negative(),
             imag: self.imag.negative(),
-            dim: self.dim,
+            derivatives: self.derivatives.map(ComplexDerivative::negative),
+            dim: self.dim.clone(),
         }
     }
 
@@ -43,13 +155,35 @@ impl Complex {
         self.imag.apply_binary_func(&self.real, f64::atan2)
     }
 
+    /// Covariance matrix `[[var(re), cov(re, im)], [cov(re, im), var(im)]]` of the value.
+    ///
+    /// Each derivative is taken as the response to a one standard deviation change in an
+    /// independent source, so the contributions of all sources simply add up.
+    pub fn covariance(&self) -> [[FloatPlus; 2]; 2] {
+        let mut var_real = FloatPlus::ZERO;
+        let mut var_imag = FloatPlus::ZERO;
+        let mut cov = FloatPlus::ZERO;
+
+        for drv in self.derivatives.values() {
+            var_real.apply_binary_func_in_place(&drv.real, |var, d| var + d * d);
+            var_imag.apply_binary_func_in_place(&drv.imag, |var, d| var + d * d);
+            cov = FloatPlus::apply_fused_func([&cov, &drv.real, &drv.imag], |[cov, dr, di]| {
+                cov + dr * di
+            });
+        }
+
+        [[var_real, cov.clone()], [cov, var_imag]]
+    }
+
     pub fn add(&self, other: &Self) -> Result<Self, ValueError> {
         if self.dim != other.dim {
-            return Err(ValueError::UnequalDimensions(self.dim, other.dim));
+            return Err(ValueError::UnequalDimensions(
+                self.dim.clone(),
+                other.dim.clone(),
+            ));
         }
-        match self.strictly_compatible(other) {
-            Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-            None => (),
+        if let Some((m, n)) = self.strictly_compatible(other) {
+            return Err(ValueError::UnequalVectorLength(m, n));
         }
 
         Ok(self.unchecked_add(other))
@@ -59,17 +193,20 @@ impl Complex {
         Self {
             real: self.real.add(&other.real),
             imag: self.imag.add(&other.imag),
-            dim: self.dim,
+            derivatives: combine_derivatives(self, other, ComplexDerivative::add),
+            dim: self.dim.clone(),
         }
     }
 
     pub fn sub(&self, other: &Self) -> Result<Self, ValueError> {
         if self.dim != other.dim {
-            return Err(ValueError::UnequalDimensions(self.dim, other.dim));
+            return Err(ValueError::UnequalDimensions(
+                self.dim.clone(),
+                other.dim.clone(),
+            ));
         }
-        match self.strictly_compatible(other) {
-            Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-            None => (),
+        if let Some((m, n)) = self.strictly_compatible(other) {
+            return Err(ValueError::UnequalVectorLength(m, n));
         }
 
         Ok(self.unchecked_sub(other))
@@ -79,115 +216,122 @@ impl Complex {
         Self {
             real: self.real.sub(&other.real),
             imag: self.imag.sub(&other.imag),
-            dim: self.dim,
+            derivatives: combine_derivatives(self, other, ComplexDerivative::sub),
+            dim: self.dim.clone(),
         }
     }
 
     pub fn mul(&self, other: &Self) -> Result<Self, ValueError> {
-        match self.strictly_compatible(other) {
-            Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-            None => (),
+        if let Some((m, n)) = self.strictly_compatible(other) {
+            return Err(ValueError::UnequalVectorLength(m, n));
         }
 
         Ok(self.unchecked_mul(other))
     }
 
     pub fn unchecked_mul(&self, other: &Self) -> Self {
+        let (real, imag) = mul_parts(&self.real, &self.imag, &other.real, &other.imag);
+
         Self {
-            real: self.real.mul(&other.real).sub(&self.imag.mul(&other.imag)),
-            imag: self.imag.mul(&other.real).add(&self.real.mul(&other.imag)),
+            real,
+            imag,
+            derivatives: combine_derivatives(self, other, |dl, dr| {
+                dl.mul_complex(&other.real, &other.imag)
+                    .add(&dr.mul_complex(&self.real, &self.imag))
+            }),
             dim: self.dim.mul(&other.dim),
         }
     }
 
     pub fn div(&self, other: &Self) -> Result<Self, ValueError> {
-        match self.strictly_compatible(other) {
-            Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-            None => (),
+        if let Some((m, n)) = self.strictly_compatible(other) {
+            return Err(ValueError::UnequalVectorLength(m, n));
         }
 
         Ok(self.unchecked_div(other))
     }
 
     pub fn unchecked_div(&self, other: &Self) -> Self {
-        let denom_factors = other.mag_si_units().square();
+        let (real, imag) = div_parts(&self.real, &self.imag, &other.real, &other.imag);
+
+        // d(l/r) = (dl - (l/r) dr) / r
+        let derivatives = combine_derivatives(self, other, |dl, dr| {
+            dl.sub(&dr.mul_complex(&real, &imag))
+                .div_complex(&other.real, &other.imag)
+        });
 
         Self {
-            real: self
-                .real
-                .mul(&other.real)
-                .add(&self.imag.mul(&other.imag))
-                .div(&denom_factors),
-            imag: self
-                .imag
-                .mul(&other.real)
-                .sub(&self.real.mul(&other.imag))
-                .div(&denom_factors),
+            real,
+            imag,
+            derivatives,
             dim: self.dim.mul(&other.dim.reciprocal()),
         }
     }
 
     pub fn exp(&self) -> Result<Self, ValueError> {
         if self.dim != SIDimension::DIMLESS {
-            return Err(ValueError::NotDimensionlessOperand(self.dim));
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
         }
 
-        let magnitude = self.real.apply_func(f64::exp);
-        let phase_real = self.imag.apply_func(f64::cos);
-        let phase_imag = self.imag.apply_func(f64::sin);
+        let parts = [&self.real, &self.imag];
+        let real = FloatPlus::apply_fused_func(parts, |[re, im]| re.exp() * im.cos());
+        let imag = FloatPlus::apply_fused_func(parts, |[re, im]| re.exp() * im.sin());
 
         Ok(Self {
-            real: magnitude.mul(&phase_real),
-            imag: magnitude.mul(&phase_imag),
+            derivatives: scale_derivatives(self, &real, &imag),
+            real,
+            imag,
             dim: SIDimension::DIMLESS,
         })
     }
 
     pub fn natlog(&self) -> Result<Self, ValueError> {
         if self.dim != SIDimension::DIMLESS {
-            return Err(ValueError::NotDimensionlessOperand(self.dim));
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
         }
 
         Ok(Self {
             real: self.mag_si_units().apply_func(f64::ln),
             imag: self.arg(),
+            derivatives: self
+                .derivatives
+                .map(|drv| drv.div_complex(&self.real, &self.imag)),
             dim: SIDimension::DIMLESS,
         })
     }
 
     pub fn cos(&self) -> Result<Self, ValueError> {
-        if self.dim != SIDimension::DIMLESS {
-            return Err(ValueError::NotDimensionlessOperand(self.dim));
+        if !self.dim.is_angle_or_dimless() {
+            return Err(ValueError::NotAngleOperand(self.dim.clone()));
         }
 
+        // -sin z, the derivative of cos z
+        let parts = [&self.real, &self.imag];
+        let dreal = FloatPlus::apply_fused_func(parts, |[re, im]| -(re.sin() * im.cosh()));
+        let dimag = FloatPlus::apply_fused_func(parts, |[re, im]| -(re.cos() * im.sinh()));
+
         Ok(Self {
-            real: self
-                .real
-                .apply_func(f64::cos)
-                .mul(&self.imag.apply_func(f64::cosh)),
-            imag: self
-                .real
-                .apply_func(f64::sin)
-                .mul(&self.imag.apply_func(f64::sinh))
-                .negative(),
+            real: FloatPlus::apply_fused_func(parts, |[re, im]| re.cos() * im.cosh()),
+            imag: FloatPlus::apply_fused_func(parts, |[re, im]| -(re.sin() * im.sinh())),
+            derivatives: scale_derivatives(self, &dreal, &dimag),
             dim: SIDimension::DIMLESS,
         })
     }
 
     pub fn sin(&self) -> Result<Self, ValueError> {
-        if self.dim != SIDimension::DIMLESS {
-            return Err(ValueError::NotDimensionlessOperand(self.dim));
+        if !self.dim.is_angle_or_dimless() {
+            return Err(ValueError::NotAngleOperand(self.dim.clone()));
         }
 
+        // cos z, the derivative of sin z
+        let parts = [&self.real, &self.imag];
+        let dreal = FloatPlus::apply_fused_func(parts, |[re, im]| re.cos() * im.cosh());
+        let dimag = FloatPlus::apply_fused_func(parts, |[re, im]| -(re.sin() * im.sinh()));
+
         Ok(Self {
-            real: self
-                .real
-                .apply_func(f64::sin)
-                .mul(&self.imag.apply_func(f64::cosh)),
-            imag: self
-                .real
-                .apply_func(f64::cos)
-                .mul(&self.imag.apply_func(f64::sinh)),
+            real: FloatPlus::apply_fused_func(parts, |[re, im]| re.sin() * im.cosh()),
+            imag: FloatPlus::apply_fused_func(parts, |[re, im]| re.cos() * im.sinh()),
+            derivatives: scale_derivatives(self, &dreal, &dimag),
             dim: SIDimension::DIMLESS,
         })
     }
@@ -195,4 +339,254 @@ impl Complex {
     pub fn tan(&self) -> Result<Self, ValueError> {
         self.sin()?.div(&self.cos()?)
     }
+
+    pub fn abs(&self) -> Quantity {
+        let mag = self.mag_si_units();
+        let derivatives = self.derivatives.map(|drv| {
+            FloatPlus::apply_fused_func(
+                [&self.real, &self.imag, &drv.real, &drv.imag, &mag],
+                |[re, im, dr, di, mag]| (re * dr + im * di) / mag,
+            )
+        });
+
+        Quantity {
+            value: mag,
+            derivatives,
+            dim: self.dim.clone(),
+        }
+    }
+
+    pub fn sqrt(&self) -> Self {
+        pow_cr(self, Rational::new(1, 2))
+    }
+
+    pub fn cbrt(&self) -> Self {
+        pow_cr(self, Rational::new(1, 3))
+    }
+
+    pub fn log10(&self) -> Result<Self, ValueError> {
+        Ok(self
+            .natlog()?
+            .unchecked_mul(&constant(std::f64::consts::LOG10_E, 0.)))
+    }
+
+    pub fn log2(&self) -> Result<Self, ValueError> {
+        Ok(self
+            .natlog()?
+            .unchecked_mul(&constant(std::f64::consts::LOG2_E, 0.)))
+    }
+
+    // sinh z = -i sin(iz)
+    pub fn sinh(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        Ok(self
+            .unchecked_mul(&constant(0., 1.))
+            .sin()?
+            .unchecked_mul(&constant(0., -1.)))
+    }
+
+    // cosh z = cos(iz)
+    pub fn cosh(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        self.unchecked_mul(&constant(0., 1.)).cos()
+    }
+
+    pub fn tanh(&self) -> Result<Self, ValueError> {
+        self.sinh()?.div(&self.cosh()?)
+    }
+
+    // asin z = -i ln(iz + sqrt(1 - z^2))
+    pub fn asin(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        // 1 - z^2, factored to keep the sign of a zero imaginary part
+        let root = one_minus(self)
+            .unchecked_mul(&self.unchecked_add(&constant(1., 0.)))
+            .sqrt();
+
+        Ok(self
+            .unchecked_mul(&constant(0., 1.))
+            .unchecked_add(&root)
+            .natlog()?
+            .unchecked_mul(&constant(0., -1.)))
+    }
+
+    // acos z = pi/2 - asin z
+    pub fn acos(&self) -> Result<Self, ValueError> {
+        Ok(constant(std::f64::consts::FRAC_PI_2, 0.).unchecked_sub(&self.asin()?))
+    }
+
+    // atan z = i/2 (ln(1 - iz) - ln(1 + iz))
+    pub fn atan(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        let iz = self.unchecked_mul(&constant(0., 1.));
+        let one = constant(1., 0.);
+
+        Ok(one
+            .unchecked_sub(&iz)
+            .natlog()?
+            .unchecked_sub(&one.unchecked_add(&iz).natlog()?)
+            .unchecked_mul(&constant(0., 0.5)))
+    }
+
+    // asinh z = ln(z + sqrt(z^2 + 1))
+    pub fn asinh(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        let root = self
+            .unchecked_mul(self)
+            .unchecked_add(&constant(1., 0.))
+            .sqrt();
+        self.unchecked_add(&root).natlog()
+    }
+
+    // acosh z = ln(z + sqrt(z + 1) sqrt(z - 1))
+    pub fn acosh(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        let one = constant(1., 0.);
+        let root = self
+            .unchecked_add(&one)
+            .sqrt()
+            .unchecked_mul(&self.unchecked_sub(&one).sqrt());
+        self.unchecked_add(&root).natlog()
+    }
+
+    // atanh z = (ln(1 + z) - ln(1 - z)) / 2
+    pub fn atanh(&self) -> Result<Self, ValueError> {
+        if self.dim != SIDimension::DIMLESS {
+            return Err(ValueError::NotDimensionlessOperand(self.dim.clone()));
+        }
+
+        let one = constant(1., 0.);
+
+        Ok(one
+            .unchecked_add(self)
+            .natlog()?
+            .unchecked_sub(&one_minus(self).natlog()?)
+            .unchecked_mul(&constant(0.5, 0.)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Complex, ComplexDerivative, Derivatives, FloatPlus, SIDimension};
+    use crate::value::SourceId;
+
+    const DZ: SourceId = SourceId(0);
+    const DW: SourceId = SourceId(1);
+
+    fn uncertain(real: f64, imag: f64, source: SourceId, dreal: f64, dimag: f64) -> Complex {
+        Complex {
+            real: FloatPlus::Scalar(real),
+            imag: FloatPlus::Scalar(imag),
+            derivatives: Derivatives::single(
+                source,
+                ComplexDerivative {
+                    real: FloatPlus::Scalar(dreal),
+                    imag: FloatPlus::Scalar(dimag),
+                },
+            ),
+            dim: SIDimension::DIMLESS,
+        }
+    }
+
+    fn scalar(x: &FloatPlus) -> f64 {
+        match x {
+            FloatPlus::Scalar(x) => *x,
+            FloatPlus::Vector(_) => panic!("{x:?} is not a scalar"),
+        }
+    }
+
+    fn check_derivative(name: &str, c: &Complex, source: SourceId, expected: (f64, f64)) {
+        let drv = c.derivatives.get(source).unwrap();
+        let found = (scalar(&drv.real), scalar(&drv.imag));
+        assert!(
+            (found.0 - expected.0).abs() < 1e-12 && (found.1 - expected.1).abs() < 1e-12,
+            "d {name} is {found:?}, expected {expected:?}"
+        );
+    }
+
+    // z = 1 + 2i moved along the real axis by 0.1 and w = 3 - i along the imaginary axis by
+    // 0.2, each by a source of its own
+    fn operands() -> (Complex, Complex) {
+        (
+            uncertain(1., 2., DZ, 0.1, 0.),
+            uncertain(3., -1., DW, 0., 0.2),
+        )
+    }
+
+    #[test]
+    fn arithmetic_derivatives() {
+        let (z, w) = operands();
+
+        let sum = z.add(&w).unwrap();
+        check_derivative("z + w", &sum, DZ, (0.1, 0.));
+        check_derivative("z + w", &sum, DW, (0., 0.2));
+
+        // w dz and z dw
+        let product = z.mul(&w).unwrap();
+        check_derivative("z w", &product, DZ, (0.3, -0.1));
+        check_derivative("z w", &product, DW, (-0.4, 0.2));
+
+        // dz / w and -z dw / w^2
+        let quotient = z.div(&w).unwrap();
+        check_derivative("z / w", &quotient, DZ, (0.03, 0.01));
+        check_derivative("z / w", &quotient, DW, (0.044, 0.008));
+    }
+
+    #[test]
+    fn function_derivatives() {
+        let (z, _) = operands();
+        let (sin, cos) = (1f64.sin(), 1f64.cos());
+        let (sinh, cosh) = (2f64.sinh(), 2f64.cosh());
+        let e = 1f64.exp();
+
+        let exp = z.exp().unwrap();
+        check_derivative(
+            "exp z",
+            &exp,
+            DZ,
+            (0.1 * e * 2f64.cos(), 0.1 * e * 2f64.sin()),
+        );
+        check_derivative("ln z", &z.natlog().unwrap(), DZ, (0.02, -0.04));
+        check_derivative(
+            "sin z",
+            &z.sin().unwrap(),
+            DZ,
+            (0.1 * cos * cosh, -0.1 * sin * sinh),
+        );
+        check_derivative(
+            "cos z",
+            &z.cos().unwrap(),
+            DZ,
+            (-0.1 * sin * cosh, -0.1 * cos * sinh),
+        );
+    }
+
+    #[test]
+    fn covariance_of_parts() {
+        let (z, w) = operands();
+
+        let [[var_real, cov], [cov_again, var_imag]] = z.mul(&w).unwrap().covariance();
+        assert!((scalar(&var_real) - 0.25).abs() < 1e-12);
+        assert!((scalar(&var_imag) - 0.05).abs() < 1e-12);
+        assert!((scalar(&cov) + 0.11).abs() < 1e-12);
+        assert_eq!(scalar(&cov), scalar(&cov_again));
+    }
 }
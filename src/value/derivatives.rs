@@ -0,0 +1,119 @@
+use super::{Derivatives, SourceId};
+
+impl<T> Default for Derivatives<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Derivatives<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn single(source: SourceId, drv: T) -> Self {
+        Self {
+            entries: vec![(source, drv)],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, source: SourceId) -> Option<&T> {
+        match self.entries.binary_search_by_key(&source, |(s, _)| *s) {
+            Ok(i) => Some(&self.entries[i].1),
+            Err(_) => None,
+        }
+    }
+
+    /// The sources along with their derivatives, in order of the sources.
+    pub fn iter(&self) -> impl Iterator<Item = (SourceId, &T)> {
+        self.entries.iter().map(|(source, drv)| (*source, drv))
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = SourceId> {
+        self.entries.iter().map(|(source, _)| *source)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|(_, drv)| drv)
+    }
+
+    /// The derivatives with respect to the same sources given by `f`, as when the value is
+    /// passed through a function of one variable.
+    pub fn map<U, F>(&self, mut f: F) -> Derivatives<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        Derivatives {
+            entries: self
+                .entries
+                .iter()
+                .map(|(source, drv)| (*source, f(drv)))
+                .collect(),
+        }
+    }
+
+    pub fn try_map<U, E, F>(&self, mut f: F) -> Result<Derivatives<U>, E>
+    where
+        F: FnMut(&T) -> Result<U, E>,
+    {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for (source, drv) in &self.entries {
+            entries.push((*source, f(drv)?));
+        }
+        Ok(Derivatives { entries })
+    }
+
+    /// The derivatives of a function of two values, `f` being given the derivatives of
+    /// each with respect to every source of either, with `zero` for a source the other
+    /// does not depend on.
+    pub fn combine<F>(&self, other: &Self, zero: &T, mut f: F) -> Self
+    where
+        F: FnMut(&T, &T) -> T,
+    {
+        let mut entries = Vec::with_capacity(self.entries.len().max(other.entries.len()));
+        let (mut lhs, mut rhs) = (
+            self.entries.iter().peekable(),
+            other.entries.iter().peekable(),
+        );
+
+        loop {
+            let entry = match (lhs.peek(), rhs.peek()) {
+                (Some((l, ld)), Some((r, rd))) if l == r => {
+                    let entry = (*l, f(ld, rd));
+                    lhs.next();
+                    rhs.next();
+                    entry
+                }
+                (Some((l, ld)), Some((r, _))) if l < r => {
+                    let entry = (*l, f(ld, zero));
+                    lhs.next();
+                    entry
+                }
+                (_, Some((r, rd))) => {
+                    let entry = (*r, f(zero, rd));
+                    rhs.next();
+                    entry
+                }
+                (Some((l, ld)), None) => {
+                    let entry = (*l, f(ld, zero));
+                    lhs.next();
+                    entry
+                }
+                (None, None) => break,
+            };
+            entries.push(entry);
+        }
+
+        Self { entries }
+    }
+}
@@ -0,0 +1,87 @@
+use super::{DimensionTable, SIDimension, ValueError};
+use crate::special::MAX_BESSEL_ORDER;
+
+impl Default for DimensionTable {
+    fn default() -> Self {
+        Self {
+            names: ["s", "m", "kg", "A", "K", "mol", "cd", "rad", "sr"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl DimensionTable {
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Registers a new base dimension, returning its index, or the existing index if the
+    /// name is already registered.
+    pub fn register(&mut self, name: &str) -> usize {
+        if let Some(index) = self.index_of(name) {
+            return index;
+        }
+
+        self.names.push(name.into());
+        self.names.len() - 1
+    }
+
+    pub fn format(&self, dim: &SIDimension) -> String {
+        let terms = dim
+            .exponents()
+            .map(|(i, e)| {
+                let name = match self.names.get(i) {
+                    Some(name) => name.clone(),
+                    None => format!("dim{i}"),
+                };
+
+                if e == 1.into() {
+                    name
+                } else if e.is_integral() {
+                    format!("{name}^{e}")
+                } else {
+                    format!("{name}^({e})")
+                }
+            })
+            .collect::<Vec<String>>();
+
+        if terms.is_empty() {
+            "1".into()
+        } else {
+            terms.join(" ")
+        }
+    }
+}
+
+impl ValueError {
+    pub fn describe(&self, dims: &DimensionTable) -> String {
+        match self {
+            Self::UnequalVectorLength(m, n) => {
+                format!("vectors have unequal lengths {m} and {n}")
+            }
+            Self::UnequalDimensions(l, r) => format!(
+                "dimensions {} and {} are not equal",
+                dims.format(l),
+                dims.format(r)
+            ),
+            Self::NotDimensionlessOperand(d) => {
+                format!("operand must be dimensionless, found {}", dims.format(d))
+            }
+            Self::NotAngleOperand(d) => format!(
+                "operand must be an angle or dimensionless, found {}",
+                dims.format(d)
+            ),
+            Self::NotIntegralOperand => "operand must be an integer".into(),
+            Self::OrderOutOfRange(n) => {
+                format!("order must be at most {MAX_BESSEL_ORDER} in size, found {n}")
+            }
+            Self::UnsupportedBaseDimension(d) => format!(
+                "base of a non-rational power must be dimensionless, found {}",
+                dims.format(d)
+            ),
+            Self::UnsupportedComplexOperand => "operand must be real".into(),
+            Self::DivisionByZero => "division by zero".into(),
+        }
+    }
+}
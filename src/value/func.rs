@@ -1,36 +1,136 @@
-use super::{Complex, Quantity, Value, ValueError};
+use super::{Complex, Quantity, Rational, Value, ValueError};
 
-fn apply_real_func<F, G>(val: &Value, qfunc: F, cfunc: G) -> Result<Value, ValueError>
+// Real values outside the domain of the real function, such as ln(-1), take the complex
+// branch instead. The domain is given as the values which are not in it, so that NaN stays
+// real.
+fn apply_real_func<D, F, G>(
+    val: &Value,
+    outside_domain: D,
+    qfunc: F,
+    cfunc: G,
+) -> Result<Value, ValueError>
 where
+    D: Fn(f64) -> bool,
     F: Fn(&Quantity) -> Result<Quantity, ValueError>,
     G: Fn(&Complex) -> Result<Complex, ValueError>,
 {
     match val.try_promote_quantity() {
-        Some(q) => Ok(qfunc(&q)?.into()),
-        None => {
+        Some(q) if !q.value.any(outside_domain) => Ok(qfunc(&q)?.into()),
+        _ => {
             let c = val.promote_to_complex();
             Ok(cfunc(&c)?.into())
         }
     }
 }
+
+fn everywhere(_: f64) -> bool {
+    false
+}
+
+fn negative(x: f64) -> bool {
+    x < 0.
+}
+
+fn beyond_unit(x: f64) -> bool {
+    x.abs() > 1.
+}
+
 impl Value {
     pub fn exp(&self) -> Result<Self, ValueError> {
-        apply_real_func(self, Quantity::exp, Complex::exp)
+        apply_real_func(self, everywhere, Quantity::exp, Complex::exp)
     }
 
     pub fn natlog(&self) -> Result<Self, ValueError> {
-        apply_real_func(self, Quantity::natlog, Complex::natlog)
+        apply_real_func(self, negative, Quantity::natlog, Complex::natlog)
     }
 
     pub fn cos(&self) -> Result<Self, ValueError> {
-        apply_real_func(self, Quantity::cos, Complex::cos)
+        apply_real_func(self, everywhere, Quantity::cos, Complex::cos)
     }
 
     pub fn sin(&self) -> Result<Self, ValueError> {
-        apply_real_func(self, Quantity::sin, Complex::sin)
+        apply_real_func(self, everywhere, Quantity::sin, Complex::sin)
     }
 
     pub fn tan(&self) -> Result<Self, ValueError> {
-        apply_real_func(self, Quantity::tan, Complex::tan)
+        apply_real_func(self, everywhere, Quantity::tan, Complex::tan)
+    }
+
+    pub fn sqrt(&self) -> Result<Self, ValueError> {
+        self.pow(&Rational::new(1, 2).into())
+    }
+
+    pub fn cbrt(&self) -> Result<Self, ValueError> {
+        self.pow(&Rational::new(1, 3).into())
+    }
+
+    pub fn abs(&self) -> Self {
+        match self {
+            &Self::Rational(r) => match r.numerator.checked_abs() {
+                Some(numerator) => Rational::new(numerator, r.denominator).into(),
+                None => Quantity::from_rational(r).abs().into(),
+            },
+            Self::Quantity(q) => q.abs().into(),
+            Self::Complex(c) => c.abs().into(),
+            Self::Mixed(m) => m.quantity.abs().into(),
+        }
+    }
+
+    pub fn log10(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, negative, Quantity::log10, Complex::log10)
+    }
+
+    pub fn log2(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, negative, Quantity::log2, Complex::log2)
+    }
+
+    pub fn log(&self, base: &Self) -> Result<Self, ValueError> {
+        self.natlog()?.div(&base.natlog()?)
+    }
+
+    pub fn asin(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, beyond_unit, Quantity::asin, Complex::asin)
+    }
+
+    pub fn acos(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, beyond_unit, Quantity::acos, Complex::acos)
+    }
+
+    pub fn atan(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, everywhere, Quantity::atan, Complex::atan)
+    }
+
+    pub fn atan2(&self, other: &Self) -> Result<Self, ValueError> {
+        match self
+            .try_promote_quantity()
+            .zip(other.try_promote_quantity())
+        {
+            Some((y, x)) => Ok(y.atan2(&x)?.into()),
+            None => Err(ValueError::UnsupportedComplexOperand),
+        }
+    }
+
+    pub fn sinh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, everywhere, Quantity::sinh, Complex::sinh)
+    }
+
+    pub fn cosh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, everywhere, Quantity::cosh, Complex::cosh)
+    }
+
+    pub fn tanh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, everywhere, Quantity::tanh, Complex::tanh)
+    }
+
+    pub fn asinh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, everywhere, Quantity::asinh, Complex::asinh)
+    }
+
+    pub fn acosh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, |x| x < 1., Quantity::acosh, Complex::acosh)
+    }
+
+    pub fn atanh(&self) -> Result<Self, ValueError> {
+        apply_real_func(self, beyond_unit, Quantity::atanh, Complex::atanh)
     }
 }
@@ -0,0 +1,247 @@
+use super::{Comparison, Complex, Derivatives, Quantity, Rational, SIDimension, Value, ValueError};
+use crate::f64plus::FloatPlus;
+use std::cmp::Ordering;
+
+// Relative difference below which floats are approximately equal regardless of uncertainty
+const APPROX_RELATIVE_TOLERANCE: f64 = 1e-9;
+
+fn compare_rationals(l: Rational, r: Rational) -> Ordering {
+    let lhs = l.numerator as i64 * r.denominator as i64;
+    let rhs = r.numerator as i64 * l.denominator as i64;
+    lhs.cmp(&rhs)
+}
+
+fn truth_float(truth: bool) -> f64 {
+    if truth { 1. } else { 0. }
+}
+
+fn element(x: &FloatPlus, i: usize) -> f64 {
+    match x {
+        FloatPlus::Scalar(s) => *s,
+        FloatPlus::Vector(v) => v[i],
+    }
+}
+
+// Picks elements of `a` where the mask is nonzero and of `b` elsewhere, scalars are
+// broadcast over the length of the mask
+fn select_floats(mask: &FloatPlus, a: &FloatPlus, b: &FloatPlus) -> FloatPlus {
+    match mask {
+        &FloatPlus::Scalar(m) => if m != 0. { a } else { b }.clone(),
+        FloatPlus::Vector(v) => FloatPlus::Vector(
+            (0..v.len())
+                .map(|i| {
+                    if v[i] != 0. {
+                        element(a, i)
+                    } else {
+                        element(b, i)
+                    }
+                })
+                .collect(),
+        ),
+    }
+}
+
+// Standard uncertainty of a quantity, each derivative being an independent source
+fn std_uncertainty(q: &Quantity) -> FloatPlus {
+    let mut var = FloatPlus::ZERO;
+    for drv in q.derivatives.values() {
+        var.apply_binary_func_in_place(drv, |var, d| var + d * d);
+    }
+    var.apply_func_in_place(f64::sqrt);
+    var
+}
+
+fn compare_quantities(l: &Quantity, r: &Quantity, op: Comparison) -> Result<FloatPlus, ValueError> {
+    if op == Comparison::Approx {
+        let difference = l.sub(r)?;
+        let sigma = std_uncertainty(&difference);
+        let magnitude = l
+            .value
+            .apply_func(f64::abs)
+            .apply_binary_func(&r.value.apply_func(f64::abs), f64::max);
+
+        let tolerance =
+            magnitude.apply_binary_func(&sigma, |m, s| (APPROX_RELATIVE_TOLERANCE * m).max(s));
+        return Ok(difference
+            .value
+            .apply_binary_func(&tolerance, |d, t| truth_float(d.abs() <= t)));
+    }
+
+    let truth = l.value.apply_binary_func(&r.value, |l, r| {
+        truth_float(match op {
+            Comparison::Less => l < r,
+            Comparison::LessEqual => l <= r,
+            Comparison::Greater => l > r,
+            Comparison::GreaterEqual => l >= r,
+            Comparison::Equal => l == r,
+            Comparison::NotEqual => l != r,
+            Comparison::Approx => unreachable!(),
+        })
+    });
+    Ok(truth)
+}
+
+// Complex values have no order, only (approximate) equality of both parts
+fn compare_complex(l: &Complex, r: &Complex, op: Comparison) -> Result<FloatPlus, ValueError> {
+    let difference = l.unchecked_sub(r);
+    let distance = difference.mag_si_units();
+
+    let truth = match op {
+        Comparison::Equal => distance.apply_func(|d| truth_float(d == 0.)),
+        Comparison::NotEqual => distance.apply_func(|d| truth_float(d != 0.)),
+        Comparison::Approx => {
+            let [[var_real, _], [_, var_imag]] = difference.covariance();
+            let sigma = var_real.add(&var_imag).apply_func(f64::sqrt);
+            let magnitude = l
+                .mag_si_units()
+                .apply_binary_func(&r.mag_si_units(), f64::max);
+            let tolerance =
+                magnitude.apply_binary_func(&sigma, |m, s| (APPROX_RELATIVE_TOLERANCE * m).max(s));
+            distance.apply_binary_func(&tolerance, |d, t| truth_float(d <= t))
+        }
+        _ => return Err(ValueError::UnsupportedComplexOperand),
+    };
+
+    Ok(truth)
+}
+
+impl Value {
+    /// The truth value as a dimensionless 1 or 0.
+    pub fn boolean(truth: bool) -> Self {
+        if truth { Rational::ONE } else { Rational::ZERO }.into()
+    }
+
+    fn from_mask(mask: FloatPlus) -> Self {
+        match mask {
+            FloatPlus::Scalar(m) => Self::boolean(m != 0.),
+            mask => Quantity {
+                value: mask,
+                derivatives: Derivatives::new(),
+                dim: SIDimension::DIMLESS,
+            }
+            .into(),
+        }
+    }
+
+    // Any nonzero dimensionless real value is true
+    fn mask(&self) -> Result<FloatPlus, ValueError> {
+        if !self.dimless() {
+            return Err(ValueError::NotDimensionlessOperand(self.dim()));
+        }
+
+        match self {
+            Self::Rational(r) => Ok(FloatPlus::Scalar(truth_float(!r.is_zero()))),
+            Self::Quantity(q) => Ok(q.value.apply_func(|x| truth_float(x != 0.))),
+            Self::Complex(_) => Err(ValueError::UnsupportedComplexOperand),
+            Self::Mixed(m) => Ok(m.quantity.value.apply_func(|x| truth_float(x != 0.))),
+        }
+    }
+
+    /// The truth of a scalar condition, or `None` when the condition is a vector mask.
+    pub fn scalar_truth(&self) -> Result<Option<bool>, ValueError> {
+        match self.mask()? {
+            FloatPlus::Scalar(m) => Ok(Some(m != 0.)),
+            FloatPlus::Vector(_) => Ok(None),
+        }
+    }
+
+    /// Compares values of the same dimension, elementwise for vectors.
+    ///
+    /// Floats are approximately equal when they differ by at most their combined standard
+    /// uncertainty, or by a relative 1e-9 when that is larger.
+    pub fn compare(&self, other: &Self, op: Comparison) -> Result<Self, ValueError> {
+        if self.dim() != other.dim() {
+            return Err(ValueError::UnequalDimensions(self.dim(), other.dim()));
+        }
+
+        if let (&Self::Rational(l), &Self::Rational(r)) = (self, other) {
+            let ordering = compare_rationals(l, r);
+            return Ok(Self::boolean(match op {
+                Comparison::Less => ordering.is_lt(),
+                Comparison::LessEqual => ordering.is_le(),
+                Comparison::Greater => ordering.is_gt(),
+                Comparison::GreaterEqual => ordering.is_ge(),
+                Comparison::Equal | Comparison::Approx => ordering.is_eq(),
+                Comparison::NotEqual => ordering.is_ne(),
+            }));
+        }
+
+        if let Some((ql, qr)) = self
+            .try_promote_quantity()
+            .zip(other.try_promote_quantity())
+        {
+            if let Some((m, n)) = ql.value.strictly_compatible(&qr.value) {
+                return Err(ValueError::UnequalVectorLength(m, n));
+            }
+            return Ok(Self::from_mask(compare_quantities(&ql, &qr, op)?));
+        }
+
+        let cl = self.promote_to_complex();
+        let cr = other.promote_to_complex();
+        if let Some((m, n)) = cl.strictly_compatible(&cr) {
+            return Err(ValueError::UnequalVectorLength(m, n));
+        }
+        Ok(Self::from_mask(compare_complex(&cl, &cr, op)?))
+    }
+
+    pub fn not(&self) -> Result<Self, ValueError> {
+        Ok(Self::from_mask(
+            self.mask()?.apply_func(|m| truth_float(m == 0.)),
+        ))
+    }
+
+    pub fn and(&self, other: &Self) -> Result<Self, ValueError> {
+        self.combine_masks(other, |l, r| l != 0. && r != 0.)
+    }
+
+    pub fn or(&self, other: &Self) -> Result<Self, ValueError> {
+        self.combine_masks(other, |l, r| l != 0. || r != 0.)
+    }
+
+    fn combine_masks<F>(&self, other: &Self, op: F) -> Result<Self, ValueError>
+    where
+        F: Fn(f64, f64) -> bool + Sync,
+    {
+        let (l, r) = (self.mask()?, other.mask()?);
+        if let Some((m, n)) = l.strictly_compatible(&r) {
+            return Err(ValueError::UnequalVectorLength(m, n));
+        }
+
+        Ok(Self::from_mask(
+            l.apply_binary_func(&r, |l, r| truth_float(op(l, r))),
+        ))
+    }
+
+    /// Elements of `if_true` where the mask `self` holds and of `if_false` elsewhere, along
+    /// with their derivatives. Both choices must have the same dimension.
+    pub fn select(&self, if_true: &Self, if_false: &Self) -> Result<Self, ValueError> {
+        let mask = self.mask()?;
+        if if_true.dim() != if_false.dim() {
+            return Err(ValueError::UnequalDimensions(if_true.dim(), if_false.dim()));
+        }
+
+        let (a, b) = match if_true
+            .try_promote_quantity()
+            .zip(if_false.try_promote_quantity())
+        {
+            Some(choices) => choices,
+            None => return Err(ValueError::UnsupportedComplexOperand),
+        };
+        for choice in [&a, &b] {
+            if let Some((m, n)) = mask.strictly_compatible(&choice.value) {
+                return Err(ValueError::UnequalVectorLength(m, n));
+            }
+        }
+
+        Ok(Quantity {
+            value: select_floats(&mask, &a.value, &b.value),
+            derivatives: a
+                .derivatives
+                .combine(&b.derivatives, &FloatPlus::ZERO, |da, db| {
+                    select_floats(&mask, da, db)
+                }),
+            dim: a.dim.clone(),
+        }
+        .into())
+    }
+}
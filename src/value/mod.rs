@@ -1,89 +1,187 @@
 use crate::{f64plus::FloatPlus, rational::Rational};
-use std::collections::HashMap;
 
 mod complex;
+mod derivatives;
+mod dimension;
 mod func;
+mod logic;
 mod ops;
 mod quantity;
+mod special;
 
-#[derive(Clone, Copy, Debug, PartialEq, Default)]
+/// Exponents of the base dimensions, indexed by position in a `DimensionTable`. Only the
+/// non-zero exponents are kept, in order of index.
+#[derive(Clone, Debug, PartialEq, Default)]
 pub struct SIDimension {
-    pub time: Rational,
-    pub length: Rational,
-    pub mass: Rational,
-    pub current: Rational,
-    pub temperature: Rational,
-    pub quantity: Rational,
-    pub luminous: Rational,
+    exponents: Vec<(usize, Rational)>,
 }
 
 impl SIDimension {
+    pub const TIME: usize = 0;
+    pub const LENGTH: usize = 1;
+    pub const MASS: usize = 2;
+    pub const CURRENT: usize = 3;
+    pub const TEMPERATURE: usize = 4;
+    pub const QUANTITY: usize = 5;
+    pub const LUMINOUS: usize = 6;
+    pub const ANGLE: usize = 7;
+    pub const SOLID_ANGLE: usize = 8;
+
     pub const DIMLESS: Self = Self {
-        time: Rational::ZERO,
-        length: Rational::ZERO,
-        mass: Rational::ZERO,
-        current: Rational::ZERO,
-        temperature: Rational::ZERO,
-        quantity: Rational::ZERO,
-        luminous: Rational::ZERO,
+        exponents: Vec::new(),
     };
 
-    pub fn mul(&self, other: &Self) -> Self {
+    pub fn base(index: usize) -> Self {
         Self {
-            time: self.time.add(other.time),
-            length: self.length.add(other.length),
-            mass: self.mass.add(other.mass),
-            current: self.current.add(other.current),
-            temperature: self.temperature.add(other.temperature),
-            quantity: self.quantity.add(other.quantity),
-            luminous: self.luminous.add(other.luminous),
+            exponents: vec![(index, Rational::ONE)],
+        }
+    }
+
+    pub fn exponent(&self, index: usize) -> Rational {
+        match self.exponents.binary_search_by_key(&index, |(i, _)| *i) {
+            Ok(i) => self.exponents[i].1,
+            Err(_) => Rational::ZERO,
         }
     }
 
+    /// The indices of the base dimensions along with their non-zero exponents.
+    pub fn exponents(&self) -> impl Iterator<Item = (usize, Rational)> + '_ {
+        self.exponents.iter().copied()
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut exponents = Vec::with_capacity(self.exponents.len() + other.exponents.len());
+        let (mut lhs, mut rhs) = (self.exponents().peekable(), other.exponents().peekable());
+
+        loop {
+            let (index, e) = match (lhs.peek(), rhs.peek()) {
+                (Some(&(l, le)), Some(&(r, re))) if l == r => {
+                    lhs.next();
+                    rhs.next();
+                    (l, le + re)
+                }
+                (Some(&(l, le)), Some(&(r, _))) if l < r => {
+                    lhs.next();
+                    (l, le)
+                }
+                (_, Some(&(r, re))) => {
+                    rhs.next();
+                    (r, re)
+                }
+                (Some(&(l, le)), None) => {
+                    lhs.next();
+                    (l, le)
+                }
+                (None, None) => break,
+            };
+
+            if !e.is_zero() {
+                exponents.push((index, e));
+            }
+        }
+
+        Self { exponents }
+    }
+
     pub fn reciprocal(&self) -> Self {
+        self.pow(Rational::ONE.negative())
+    }
+
+    pub fn pow(&self, e: Rational) -> Self {
+        if e.is_zero() {
+            return Self::DIMLESS;
+        }
+
         Self {
-            time: self.time.negative(),
-            length: self.length.negative(),
-            mass: self.mass.negative(),
-            current: self.current.negative(),
-            temperature: self.temperature.negative(),
-            quantity: self.quantity.negative(),
-            luminous: self.luminous.negative(),
+            exponents: self.exponents().map(|(i, x)| (i, x * e)).collect(),
         }
     }
 
-    pub fn pow(&self, e: Rational) -> Self {
+    /// Whether the dimension is that of a plane angle or none at all, as the trigonometric
+    /// functions take.
+    pub fn is_angle_or_dimless(&self) -> bool {
+        self.exponents.is_empty() || *self == Self::base(Self::ANGLE)
+    }
+
+    /// The dimension with plane and solid angle treated as dimensionless, as they are
+    /// unless angles are tracked by the environment.
+    pub fn without_angles(&self) -> Self {
         Self {
-            time: self.time.mul(e),
-            length: self.length.mul(e),
-            mass: self.mass.mul(e),
-            current: self.current.mul(e),
-            temperature: self.temperature.mul(e),
-            quantity: self.quantity.mul(e),
-            luminous: self.luminous.mul(e),
+            exponents: self
+                .exponents()
+                .filter(|(i, _)| *i != Self::ANGLE && *i != Self::SOLID_ANGLE)
+                .collect(),
         }
     }
 }
 
+/// Names of the base dimensions, the SI seven along with plane and solid angle by default.
+#[derive(Clone, Debug)]
+pub struct DimensionTable {
+    pub names: Vec<String>,
+}
+
+/// An independent source of uncertainty, as numbered by the `SourceRegistry` of an
+/// environment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceId(pub u32);
+
+/// Derivatives with respect to each source of uncertainty, kept in order of the sources so
+/// that those of two values are combined in a single pass.
+#[derive(Clone, Debug)]
+pub struct Derivatives<T> {
+    entries: Vec<(SourceId, T)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Quantity {
     pub value: FloatPlus,
-    pub derivatives: HashMap<String, FloatPlus>,
+    pub derivatives: Derivatives<FloatPlus>,
     pub dim: SIDimension,
 }
 
+#[derive(Clone, Debug)]
+pub struct ComplexDerivative {
+    pub real: FloatPlus,
+    pub imag: FloatPlus,
+}
+
 #[derive(Clone, Debug)]
 pub struct Complex {
     pub real: FloatPlus,
     pub imag: FloatPlus,
+    pub derivatives: Derivatives<ComplexDerivative>,
     pub dim: SIDimension,
 }
 
+/// Comparisons of values, which give a dimensionless 1 when they hold and 0 otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Approx,
+}
+
+/// A quantity split into amounts of several units for display, such as 1 h 20 min 5 s.
+/// Any operation on it uses the quantity itself.
+#[derive(Clone, Debug)]
+pub struct MixedAmounts {
+    pub quantity: Quantity,
+    /// The dimensionless amount of each unit with the unit as written, like `[min]`. All
+    /// but the last amount are whole, and the last carries the uncertainty.
+    pub parts: Vec<(Quantity, String)>,
+}
+
 #[derive(Clone, Debug)]
 pub enum Value {
     Rational(Rational),
     Quantity(Quantity),
     Complex(Complex),
+    Mixed(MixedAmounts),
 }
 
 impl From<Rational> for Value {
@@ -105,11 +203,21 @@ impl From<Complex> for Value {
 }
 
 impl Value {
+    pub fn dim(&self) -> SIDimension {
+        match self {
+            Self::Rational(_) => SIDimension::DIMLESS,
+            Self::Quantity(q) => q.dim.clone(),
+            Self::Complex(c) => c.dim.clone(),
+            Self::Mixed(m) => m.quantity.dim.clone(),
+        }
+    }
+
     pub fn dimless(&self) -> bool {
         match self {
             Self::Rational(_) => true,
             Self::Quantity(q) => q.dim == SIDimension::DIMLESS,
             Self::Complex(c) => c.dim == SIDimension::DIMLESS,
+            Self::Mixed(m) => m.quantity.dim == SIDimension::DIMLESS,
         }
     }
 
@@ -118,14 +226,16 @@ impl Value {
             Self::Rational(r) => Some(Quantity::from_rational(*r)),
             Self::Quantity(q) => Some(q.clone()),
             Self::Complex(_) => None,
+            Self::Mixed(m) => Some(m.quantity.clone()),
         }
     }
 
     pub fn promote_to_complex(&self) -> Complex {
         match self {
             Self::Rational(r) => Complex::from_rational(*r),
-            Self::Quantity(q) => Complex::from_quantity(&q),
+            Self::Quantity(q) => Complex::from_quantity(q),
             Self::Complex(c) => c.clone(),
+            Self::Mixed(m) => Complex::from_quantity(&m.quantity),
         }
     }
 }
@@ -135,6 +245,11 @@ pub enum ValueError {
     UnequalVectorLength(usize, usize),
     UnequalDimensions(SIDimension, SIDimension),
     NotDimensionlessOperand(SIDimension),
+    NotAngleOperand(SIDimension),
+    NotIntegralOperand,
+    /// The order of a Bessel function is beyond `special::MAX_BESSEL_ORDER`
+    OrderOutOfRange(i32),
     UnsupportedBaseDimension(SIDimension),
+    UnsupportedComplexOperand,
     DivisionByZero,
 }
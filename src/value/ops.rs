@@ -1,6 +1,5 @@
-use super::{Complex, Quantity, Rational, SIDimension, Value, ValueError};
+use super::{Complex, Derivatives, Quantity, Rational, SIDimension, SourceId, Value, ValueError};
 use crate::f64plus::FloatPlus;
-use std::collections::HashMap;
 
 fn apply_value_binary_op<F, G, H>(
     lhs: &Value,
@@ -14,17 +13,14 @@ where
     G: Fn(&Quantity, &Quantity) -> Result<Quantity, ValueError>,
     H: Fn(&Complex, &Complex) -> Result<Complex, ValueError>,
 {
-    match (lhs, rhs) {
-        (&Value::Rational(l), &Value::Rational(r)) => match rational_op(l, r) {
-            Some(result) => return Ok(result.into()),
-            _ => (),
-        },
-        _ => (),
+    if let (&Value::Rational(l), &Value::Rational(r)) = (lhs, rhs)
+        && let Some(result) = rational_op(l, r)
+    {
+        return Ok(result.into());
     }
 
-    match lhs.try_promote_quantity().zip(rhs.try_promote_quantity()) {
-        Some((ql, qr)) => return Ok(quantity_op(&ql, &qr)?.into()),
-        None => (),
+    if let Some((ql, qr)) = lhs.try_promote_quantity().zip(rhs.try_promote_quantity()) {
+        return Ok(quantity_op(&ql, &qr)?.into());
     }
 
     let cl = lhs.promote_to_complex();
@@ -37,11 +33,12 @@ impl Value {
     pub fn negative(&self) -> Self {
         match self {
             &Self::Rational(r) => match r.checked_negative() {
-                Some(result) => return result.into(),
+                Some(result) => result.into(),
                 None => Quantity::from_rational(r).negative().into(),
             },
             Self::Quantity(q) => q.negative().into(),
             Self::Complex(c) => c.negative().into(),
+            Self::Mixed(m) => m.quantity.negative().into(),
         }
     }
 
@@ -65,6 +62,22 @@ impl Value {
         )
     }
 
+    /// `self ± sigma`, where `sigma` is the standard uncertainty from the independent
+    /// `source`. Only the magnitude of `sigma` is used, its own uncertainty is ignored.
+    pub fn plus_minus(&self, sigma: &Self, source: SourceId) -> Result<Self, ValueError> {
+        let sigma = match sigma.try_promote_quantity() {
+            Some(q) => q,
+            None => return Err(ValueError::UnsupportedComplexOperand),
+        };
+
+        let uncertainty = Quantity {
+            value: sigma.value.apply_func(|_| 0.),
+            derivatives: Derivatives::single(source, sigma.value.apply_func(f64::abs)),
+            dim: sigma.dim.clone(),
+        };
+        self.add(&uncertainty.into())
+    }
+
     pub fn mul(&self, other: &Self) -> Result<Self, ValueError> {
         apply_value_binary_op(
             self,
@@ -107,12 +120,14 @@ impl Value {
                 }
                 Self::Quantity(b) => Ok(pow_qr(b, e)),
                 Self::Complex(b) => Ok(pow_cr(b, e).into()),
+                Self::Mixed(b) => Ok(pow_qr(&b.quantity, e)),
             },
             Self::Quantity(e) => match self.try_promote_quantity() {
                 Some(b) => pow_qq(&b, e),
                 None => Ok(pow_cc(&self.promote_to_complex(), &Complex::from_quantity(e))?.into()),
             },
             Self::Complex(e) => Ok(pow_cc(&self.promote_to_complex(), e)?.into()),
+            Self::Mixed(e) => self.pow(&e.quantity.clone().into()),
         }
     }
 }
@@ -134,26 +149,18 @@ fn pow_ri(base: Rational, index: i32) -> Result<Rational, ValueError> {
 
 fn pow_qr(base: &Quantity, index: Rational) -> Value {
     // Negative raised to odd denominators are treated differently
-    if base.value.any(|x| x < 0.) && index.denominator % 2 == 0 {
+    if base.value.any(|x| x < 0.) && index.denominator.is_multiple_of(2) {
         return pow_cr(&Complex::from_quantity(base), index).into();
     }
 
     let result_value = pow_fpr(&base.value, index);
 
-    let index_minus_one = index.sub(Rational::ONE);
-    let mut result_derivatives = HashMap::<String, FloatPlus>::new();
-    for (var, drv) in &base.derivatives {
-        result_derivatives.insert(
-            var.clone(),
-            FloatPlus::Scalar(index.into())
-                .mul(&pow_fpr(&base.value, index_minus_one))
-                .mul(&drv),
-        );
-    }
+    let index_minus_one = index - Rational::ONE;
+    let slope = FloatPlus::Scalar(index.into()).mul(&pow_fpr(&base.value, index_minus_one));
 
     Quantity {
         value: result_value,
-        derivatives: result_derivatives,
+        derivatives: base.derivatives.map(|drv| slope.mul(drv)),
         dim: base.dim.pow(index),
     }
     .into()
@@ -169,7 +176,7 @@ fn pow_fpr(base: &FloatPlus, index: Rational) -> FloatPlus {
     }
 }
 
-fn pow_cr(base: &Complex, index: Rational) -> Complex {
+pub(super) fn pow_cr(base: &Complex, index: Rational) -> Complex {
     let mag = base.mag_si_units();
     let arg = base.arg();
 
@@ -178,28 +185,39 @@ fn pow_cr(base: &Complex, index: Rational) -> Complex {
     let phase_real = result_arg.apply_func(f64::cos);
     let phase_imag = result_arg.apply_func(f64::sin);
 
+    let real = result_mag.mul(&phase_real);
+    let imag = result_mag.mul(&phase_imag);
+
+    // d(z^n) = n z^n / z dz
+    let index_factor = FloatPlus::Scalar(index.into());
+    let derivatives = base.derivatives.map(|drv| {
+        drv.mul_complex(&real, &imag)
+            .div_complex(&base.real, &base.imag)
+            .mul_complex(&index_factor, &FloatPlus::ZERO)
+    });
+
     Complex {
-        real: result_mag.mul(&phase_real),
-        imag: result_mag.mul(&phase_imag),
+        real,
+        imag,
+        derivatives,
         dim: base.dim.pow(index),
     }
 }
 
 fn pow_cc(base: &Complex, index: &Complex) -> Result<Complex, ValueError> {
-    match base.strictly_compatible(index) {
-        Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-        None => (),
+    if let Some((m, n)) = base.strictly_compatible(index) {
+        return Err(ValueError::UnequalVectorLength(m, n));
     }
 
     if index.dim != SIDimension::DIMLESS {
-        return Err(ValueError::NotDimensionlessOperand(index.dim));
+        return Err(ValueError::NotDimensionlessOperand(index.dim.clone()));
     }
     if base.dim != SIDimension::DIMLESS {
-        return Err(ValueError::UnsupportedBaseDimension(base.dim));
+        return Err(ValueError::UnsupportedBaseDimension(base.dim.clone()));
     }
 
     // z^w = exp(w ln z)
-    Ok(base.natlog().unwrap().unchecked_mul(&index).exp().unwrap())
+    Ok(base.natlog().unwrap().unchecked_mul(index).exp().unwrap())
 }
 
 fn pow_qq(base: &Quantity, index: &Quantity) -> Result<Value, ValueError> {
@@ -211,51 +229,31 @@ fn pow_qq(base: &Quantity, index: &Quantity) -> Result<Value, ValueError> {
         .into());
     }
 
-    match base.value.strictly_compatible(&index.value) {
-        Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-        None => (),
+    if let Some((m, n)) = base.value.strictly_compatible(&index.value) {
+        return Err(ValueError::UnequalVectorLength(m, n));
     }
 
     if index.dim != SIDimension::DIMLESS {
-        return Err(ValueError::NotDimensionlessOperand(index.dim));
+        return Err(ValueError::NotDimensionlessOperand(index.dim.clone()));
     }
     if base.dim != SIDimension::DIMLESS {
-        return Err(ValueError::UnsupportedBaseDimension(base.dim));
+        return Err(ValueError::UnsupportedBaseDimension(base.dim.clone()));
     }
 
     let result_value = base.value.apply_binary_func(&index.value, f64::powf);
-    let mut result_derivatives = HashMap::<String, FloatPlus>::new();
-
-    for (var, base_drv) in &base.derivatives {
-        let index_drv = index.derivatives.get(var).unwrap_or(&FloatPlus::ZERO);
-        result_derivatives.insert(
-            var.clone(),
+    let result_derivatives = base.derivatives.combine(
+        &index.derivatives,
+        &FloatPlus::ZERO,
+        |base_drv, index_drv| {
             derivative_pow_qq(
                 &base.value,
                 base_drv,
                 &index.value,
                 index_drv,
                 &result_value,
-            ),
-        );
-    }
-
-    for (var, index_drv) in &index.derivatives {
-        if base.derivatives.contains_key(var) {
-            continue;
-        }
-
-        result_derivatives.insert(
-            var.clone(),
-            derivative_pow_qq(
-                &base.value,
-                &FloatPlus::ZERO,
-                &index.value,
-                index_drv,
-                &result_value,
-            ),
-        );
-    }
+            )
+        },
+    );
 
     Ok(Quantity {
         value: result_value,
@@ -274,5 +272,5 @@ fn derivative_pow_qq(
 ) -> FloatPlus {
     de.mul(&b.apply_func(f64::ln))
         .add(&e.mul(db).div(b))
-        .mul(&pow)
+        .mul(pow)
 }
@@ -1,28 +1,43 @@
-use super::{Quantity, Rational, SIDimension, ValueError};
+use super::{Derivatives, Quantity, Rational, SIDimension, ValueError};
 use crate::f64plus::FloatPlus;
-use std::collections::HashMap;
 
-fn apply_dimless_func<F, D>(q: &Quantity, f: F, df: D) -> Result<Quantity, ValueError>
+pub(super) fn apply_dimless_func<F, D>(q: &Quantity, f: F, df: D) -> Result<Quantity, ValueError>
 where
-    F: Fn(f64) -> f64,
-    D: Fn(f64) -> f64,
+    F: Fn(f64) -> f64 + Sync,
+    D: Fn(f64) -> f64 + Sync,
 {
     if q.dim != SIDimension::DIMLESS {
-        return Err(ValueError::NotDimensionlessOperand(q.dim));
+        return Err(ValueError::NotDimensionlessOperand(q.dim.clone()));
     }
 
-    let value = q.value.apply_func(&f);
-    let mut derivatives = HashMap::new();
+    Ok(apply_func(q, f, df))
+}
 
-    for (var, drv) in &q.derivatives {
-        derivatives.insert(var.clone(), q.value.apply_func(&df).mul(&drv));
+fn apply_angle_func<F, D>(q: &Quantity, f: F, df: D) -> Result<Quantity, ValueError>
+where
+    F: Fn(f64) -> f64 + Sync,
+    D: Fn(f64) -> f64 + Sync,
+{
+    if !q.dim.is_angle_or_dimless() {
+        return Err(ValueError::NotAngleOperand(q.dim.clone()));
     }
 
-    Ok(Quantity {
+    Ok(apply_func(q, f, df))
+}
+
+fn apply_func<F, D>(q: &Quantity, f: F, df: D) -> Quantity
+where
+    F: Fn(f64) -> f64 + Sync,
+    D: Fn(f64) -> f64 + Sync,
+{
+    let value = q.value.apply_func(&f);
+    let slope = q.value.apply_func(&df);
+
+    Quantity {
         value,
-        derivatives,
+        derivatives: q.derivatives.map(|drv| slope.mul(drv)),
         dim: SIDimension::DIMLESS,
-    })
+    }
 }
 
 fn apply_binary_op<F, G, H>(
@@ -38,29 +53,16 @@ where
     H: Fn(&SIDimension, &SIDimension) -> Result<SIDimension, ValueError>,
 {
     let dim = dim_op(&lhs.dim, &rhs.dim)?;
-    match lhs.value.strictly_compatible(&rhs.value) {
-        Some((m, n)) => return Err(ValueError::UnequalVectorLength(m, n)),
-        None => (),
+    if let Some((m, n)) = lhs.value.strictly_compatible(&rhs.value) {
+        return Err(ValueError::UnequalVectorLength(m, n));
     }
     let value = op(&lhs.value, &rhs.value);
 
-    let mut derivatives = HashMap::<String, FloatPlus>::new();
-
-    for (var, lhs_drv) in &lhs.derivatives {
-        let rhs_drv = rhs.derivatives.get(var).unwrap_or(&FloatPlus::ZERO);
-        derivatives.insert(var.clone(), dop(&lhs.value, lhs_drv, &rhs.value, rhs_drv));
-    }
-
-    for (var, rhs_drv) in &rhs.derivatives {
-        if lhs.derivatives.contains_key(var) {
-            continue;
-        }
-
-        derivatives.insert(
-            var.clone(),
-            dop(&lhs.value, &FloatPlus::ZERO, &rhs.value, rhs_drv),
-        );
-    }
+    let derivatives =
+        lhs.derivatives
+            .combine(&rhs.derivatives, &FloatPlus::ZERO, |lhs_drv, rhs_drv| {
+                dop(&lhs.value, lhs_drv, &rhs.value, rhs_drv)
+            });
 
     Ok(Quantity {
         value,
@@ -73,22 +75,24 @@ impl Quantity {
     pub fn from_rational(r: Rational) -> Self {
         Quantity {
             value: FloatPlus::Scalar(r.to_float()),
-            derivatives: HashMap::new(),
+            derivatives: Derivatives::new(),
             dim: SIDimension::DIMLESS,
         }
     }
 
-    pub fn negative(&self) -> Self {
-        let mut derivatives = HashMap::new();
-
-        for (var, drv) in &self.derivatives {
-            derivatives.insert(var.clone(), drv.negative());
+    pub fn from_float(x: f64) -> Self {
+        Quantity {
+            value: FloatPlus::Scalar(x),
+            derivatives: Derivatives::new(),
+            dim: SIDimension::DIMLESS,
         }
+    }
 
+    pub fn negative(&self) -> Self {
         Self {
             value: self.value.negative(),
-            derivatives,
-            dim: self.dim,
+            derivatives: self.derivatives.map(FloatPlus::negative),
+            dim: self.dim.clone(),
         }
     }
 
@@ -98,9 +102,9 @@ impl Quantity {
             other,
             FloatPlus::add,
             |_, dl, _, dr| dl.add(dr),
-            |&l, &r| {
+            |l, r| {
                 if l != r {
-                    Err(ValueError::UnequalDimensions(l, r))
+                    Err(ValueError::UnequalDimensions(l.clone(), r.clone()))
                 } else {
                     Ok(l.clone())
                 }
@@ -114,9 +118,9 @@ impl Quantity {
             other,
             FloatPlus::sub,
             |_, dl, _, dr| dl.sub(dr),
-            |&l, &r| {
+            |l, r| {
                 if l != r {
-                    Err(ValueError::UnequalDimensions(l, r))
+                    Err(ValueError::UnequalDimensions(l.clone(), r.clone()))
                 } else {
                     Ok(l.clone())
                 }
@@ -129,7 +133,9 @@ impl Quantity {
             self,
             other,
             FloatPlus::mul,
-            |l, dl, r, dr| dl.mul(r).add(&l.mul(dr)),
+            |l, dl, r, dr| {
+                FloatPlus::apply_fused_func([l, dl, r, dr], |[l, dl, r, dr]| dl * r + l * dr)
+            },
             |l, r| Ok(l.mul(r)),
         )
     }
@@ -139,7 +145,11 @@ impl Quantity {
             self,
             other,
             FloatPlus::div,
-            |l, dl, r, dr| dl.mul(r).sub(&l.mul(dr)).div(&r.square()),
+            |l, dl, r, dr| {
+                FloatPlus::apply_fused_func([l, dl, r, dr], |[l, dl, r, dr]| {
+                    (dl * r - l * dr) / (r * r)
+                })
+            },
             |l, r| Ok(l.mul(&r.reciprocal())),
         )
     }
@@ -149,21 +159,99 @@ impl Quantity {
     }
 
     pub fn cos(&self) -> Result<Self, ValueError> {
-        apply_dimless_func(self, f64::cos, |x| -x.sin())
+        apply_angle_func(self, f64::cos, |x| -x.sin())
     }
 
     pub fn sin(&self) -> Result<Self, ValueError> {
-        apply_dimless_func(self, f64::sin, f64::cos)
+        apply_angle_func(self, f64::sin, f64::cos)
     }
 
     pub fn tan(&self) -> Result<Self, ValueError> {
-        apply_dimless_func(self, f64::tan, |x| {
-            let sinx = x.sin();
-            1. / (sinx * sinx)
+        apply_angle_func(self, f64::tan, |x| {
+            let cosx = x.cos();
+            1. / (cosx * cosx)
         })
     }
 
     pub fn natlog(&self) -> Result<Self, ValueError> {
         apply_dimless_func(self, f64::ln, |x| 1. / x)
     }
+
+    pub fn log10(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::log10, |x| 1. / (x * std::f64::consts::LN_10))
+    }
+
+    pub fn log2(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::log2, |x| 1. / (x * std::f64::consts::LN_2))
+    }
+
+    pub fn abs(&self) -> Self {
+        let sign = self.value.apply_func(f64::signum);
+
+        Self {
+            value: self.value.apply_func(f64::abs),
+            derivatives: self.derivatives.map(|drv| sign.mul(drv)),
+            dim: self.dim.clone(),
+        }
+    }
+
+    pub fn asin(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::asin, |x| 1. / (1. - x * x).sqrt())
+    }
+
+    pub fn acos(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::acos, |x| -1. / (1. - x * x).sqrt())
+    }
+
+    pub fn atan(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::atan, |x| 1. / (1. + x * x))
+    }
+
+    pub fn atan2(&self, other: &Self) -> Result<Self, ValueError> {
+        // d atan2(y, x) = (x dy - y dx) / (x^2 + y^2)
+        apply_binary_op(
+            self,
+            other,
+            |y, x| y.apply_binary_func(x, f64::atan2),
+            |y, dy, x, dx| {
+                FloatPlus::apply_fused_func([y, dy, x, dx], |[y, dy, x, dx]| {
+                    (x * dy - y * dx) / (x * x + y * y)
+                })
+            },
+            |l, r| {
+                if l != r {
+                    Err(ValueError::UnequalDimensions(l.clone(), r.clone()))
+                } else {
+                    Ok(SIDimension::DIMLESS)
+                }
+            },
+        )
+    }
+
+    pub fn sinh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::sinh, f64::cosh)
+    }
+
+    pub fn cosh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::cosh, f64::sinh)
+    }
+
+    pub fn tanh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::tanh, |x| {
+            let coshx = x.cosh();
+            1. / (coshx * coshx)
+        })
+    }
+
+    pub fn asinh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::asinh, |x| 1. / (x * x + 1.).sqrt())
+    }
+
+    pub fn acosh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::acosh, |x| 1. / (x * x - 1.).sqrt())
+    }
+
+    pub fn atanh(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, f64::atanh, |x| 1. / (1. - x * x))
+    }
 }
@@ -0,0 +1,200 @@
+use super::{Quantity, SIDimension, Value, ValueError, quantity::apply_dimless_func};
+use crate::{f64plus::FloatPlus, special};
+
+fn apply_special_func<F>(val: &Value, qfunc: F) -> Result<Value, ValueError>
+where
+    F: Fn(&Quantity) -> Result<Quantity, ValueError>,
+{
+    match val.try_promote_quantity() {
+        Some(q) => Ok(qfunc(&q)?.into()),
+        None => Err(ValueError::UnsupportedComplexOperand),
+    }
+}
+
+fn integer_order(order: &Value) -> Result<i32, ValueError> {
+    match order {
+        Value::Rational(r) => {
+            if r.is_integral() {
+                Ok(r.numerator)
+            } else {
+                Err(ValueError::NotIntegralOperand)
+            }
+        }
+        Value::Quantity(q) => {
+            if q.dim != SIDimension::DIMLESS {
+                return Err(ValueError::NotDimensionlessOperand(q.dim.clone()));
+            }
+
+            match q.value {
+                FloatPlus::Scalar(x) if x.fract() == 0. && x.abs() <= i32::MAX as f64 => {
+                    Ok(x as i32)
+                }
+                _ => Err(ValueError::NotIntegralOperand),
+            }
+        }
+        Value::Complex(_) => Err(ValueError::UnsupportedComplexOperand),
+        Value::Mixed(m) => integer_order(&m.quantity.clone().into()),
+    }
+}
+
+// The neighbouring orders used by the derivative, for an order small enough that the
+// recurrences finish in reasonable time
+fn neighbouring_orders(n: i32) -> Result<(i32, i32), ValueError> {
+    match (n.checked_sub(1), n.checked_add(1)) {
+        (Some(below), Some(above)) if n.unsigned_abs() <= special::MAX_BESSEL_ORDER => {
+            Ok((below, above))
+        }
+        _ => Err(ValueError::OrderOutOfRange(n)),
+    }
+}
+
+impl Quantity {
+    pub fn gamma(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::gamma, |x| {
+            special::gamma(x) * special::digamma(x)
+        })
+    }
+
+    pub fn lgamma(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::lgamma, special::digamma)
+    }
+
+    pub fn erf(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::erf, |x| {
+            std::f64::consts::FRAC_2_SQRT_PI * (-x * x).exp()
+        })
+    }
+
+    pub fn erfc(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::erfc, |x| {
+            -std::f64::consts::FRAC_2_SQRT_PI * (-x * x).exp()
+        })
+    }
+
+    pub fn bessel_j(&self, n: i32) -> Result<Self, ValueError> {
+        let (below, above) = neighbouring_orders(n)?;
+        apply_dimless_func(
+            self,
+            |x| special::bessel_j(n, x),
+            |x| (special::bessel_j(below, x) - special::bessel_j(above, x)) / 2.,
+        )
+    }
+
+    pub fn bessel_y(&self, n: i32) -> Result<Self, ValueError> {
+        let (below, above) = neighbouring_orders(n)?;
+        apply_dimless_func(
+            self,
+            |x| special::bessel_y(n, x),
+            |x| (special::bessel_y(below, x) - special::bessel_y(above, x)) / 2.,
+        )
+    }
+
+    pub fn bessel_i(&self, n: i32) -> Result<Self, ValueError> {
+        let (below, above) = neighbouring_orders(n)?;
+        apply_dimless_func(
+            self,
+            |x| special::bessel_i(n, x),
+            |x| (special::bessel_i(below, x) + special::bessel_i(above, x)) / 2.,
+        )
+    }
+
+    pub fn bessel_k(&self, n: i32) -> Result<Self, ValueError> {
+        let (below, above) = neighbouring_orders(n)?;
+        apply_dimless_func(
+            self,
+            |x| special::bessel_k(n, x),
+            |x| -(special::bessel_k(below, x) + special::bessel_k(above, x)) / 2.,
+        )
+    }
+
+    pub fn airy_ai(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::airy_ai, special::airy_ai_prime)
+    }
+
+    pub fn airy_bi(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::airy_bi, special::airy_bi_prime)
+    }
+
+    // Ai''(x) = x Ai(x), likewise for Bi
+    pub fn airy_ai_prime(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::airy_ai_prime, |x| x * special::airy_ai(x))
+    }
+
+    pub fn airy_bi_prime(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::airy_bi_prime, |x| x * special::airy_bi(x))
+    }
+
+    pub fn lambert_w0(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::lambert_w0, |x| {
+            special::lambert_w_derivative(special::lambert_w0(x))
+        })
+    }
+
+    pub fn lambert_wm1(&self) -> Result<Self, ValueError> {
+        apply_dimless_func(self, special::lambert_wm1, |x| {
+            special::lambert_w_derivative(special::lambert_wm1(x))
+        })
+    }
+}
+
+impl Value {
+    pub fn gamma(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::gamma)
+    }
+
+    pub fn lgamma(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::lgamma)
+    }
+
+    pub fn erf(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::erf)
+    }
+
+    pub fn erfc(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::erfc)
+    }
+
+    pub fn bessel_j(&self, order: &Self) -> Result<Self, ValueError> {
+        let n = integer_order(order)?;
+        apply_special_func(self, |q| q.bessel_j(n))
+    }
+
+    pub fn bessel_y(&self, order: &Self) -> Result<Self, ValueError> {
+        let n = integer_order(order)?;
+        apply_special_func(self, |q| q.bessel_y(n))
+    }
+
+    pub fn bessel_i(&self, order: &Self) -> Result<Self, ValueError> {
+        let n = integer_order(order)?;
+        apply_special_func(self, |q| q.bessel_i(n))
+    }
+
+    pub fn bessel_k(&self, order: &Self) -> Result<Self, ValueError> {
+        let n = integer_order(order)?;
+        apply_special_func(self, |q| q.bessel_k(n))
+    }
+
+    pub fn airy_ai(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::airy_ai)
+    }
+
+    pub fn airy_bi(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::airy_bi)
+    }
+
+    pub fn airy_ai_prime(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::airy_ai_prime)
+    }
+
+    pub fn airy_bi_prime(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::airy_bi_prime)
+    }
+
+    pub fn lambert_w0(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::lambert_w0)
+    }
+
+    pub fn lambert_wm1(&self) -> Result<Self, ValueError> {
+        apply_special_func(self, Quantity::lambert_wm1)
+    }
+}